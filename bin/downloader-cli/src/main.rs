@@ -1,5 +1,8 @@
 use anyhow::anyhow;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+
+mod nfo;
+mod search;
 static mut RT: Option<&tokio::runtime::Runtime> = None;
 
 pub fn rt() -> &'static tokio::runtime::Runtime {
@@ -8,30 +11,197 @@ pub fn rt() -> &'static tokio::runtime::Runtime {
 
 const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
 const CONN_POOL_SIZE: u8 = 8;
+/// between danmaku history requests, a video of years takes hundreds of them
+const HISTORY_PAUSE: std::time::Duration = std::time::Duration::from_millis(500);
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[arg(short, long)]
     debug: bool,
+    /// login cookies, a `SESSDATA=..; bili_jct=..` string or a cookies.txt/json file
+    #[arg(short, long, global = true)]
+    cookie: Option<String>,
+    /// max download speed of all files in bytes/sec, with optional K/M/G suffix like `2M`
+    #[arg(long, global = true, value_parser = parse_rate)]
+    limit_rate: Option<u64>,
+    /// proxy of every request, `http://`, `https://`, `socks5://` or `socks5h://`
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+    /// proxy of bilibili apis, overrides `--proxy`, `direct` to bypass it
+    #[arg(long, global = true)]
+    api_proxy: Option<String>,
+    /// proxy of video and image cdns, overrides `--proxy`, `direct` to bypass it
+    #[arg(long, global = true)]
+    cdn_proxy: Option<String>,
+    /// save every api response into a cassette file, login secrets are scrubbed
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<std::path::PathBuf>,
+    /// answer api calls from a cassette file made by `--record` instead of the network
+    #[arg(long, global = true, value_name = "FILE")]
+    replay: Option<std::path::PathBuf>,
+    #[command(flatten)]
+    sidecars: Sidecars,
     #[command(subcommand)]
     command: Commands,
 }
 
+/// metadata files written next to every video
+#[derive(Args, Debug, Clone)]
+struct Sidecars {
+    /// write the video metadata to `{video}.info.json`
+    #[arg(long, global = true)]
+    write_info_json: bool,
+    /// write a kodi `{video}.nfo`, and a `tvshow.nfo` into season folders
+    #[arg(long, global = true)]
+    write_nfo: bool,
+    /// save the cover to `{video}-poster.jpg`, and a `folder.jpg` into season folders
+    #[arg(long, global = true)]
+    write_thumbnail: bool,
+    /// embed the cover into the mp4 as its thumbnail
+    #[arg(long, global = true)]
+    embed_thumbnail: bool,
+    /// save subtitles of these languages to `{video}.{lang}.{format}`, like `zh-CN,ai-zh` or `all`
+    #[arg(long, global = true, value_delimiter = ',')]
+    sub_langs: Option<Vec<String>>,
+    /// format of subtitles, `srt`, `vtt` or `ass`
+    #[arg(long, global = true, default_value = "srt", value_parser = parse_sub_format)]
+    sub_format: SubtitleFormat,
+    /// render danmaku into `{video}.danmaku.ass`
+    #[arg(long, global = true)]
+    write_danmaku: bool,
+}
+
+impl Sidecars {
+    /// the video detail is fetched only when some file needs it
+    fn needs_detail(&self) -> bool {
+        self.write_info_json || self.write_nfo || self.write_thumbnail || self.embed_thumbnail
+    }
+
+    fn needs_cover(&self) -> bool {
+        self.write_thumbnail || self.embed_thumbnail
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// download bilibili with {av} num
-    AV { aid: Vec<u64> },
+    AV {
+        aid: Vec<u64>,
+        #[command(flatten)]
+        pages: PageArgs,
+    },
     /// download bilibili with {bv} num
-    BV { bvid: Vec<String> },
+    BV {
+        bvid: Vec<String>,
+        #[command(flatten)]
+        pages: PageArgs,
+    },
     /// download season with any {av}/{bv}
     Season {
         /// autodetected {av} or {bv}
         id: Vec<String>,
     },
+    /// download danmaku of {av}/{bv} without the video, history snapshots need login
+    Danmaku {
+        /// autodetected {av} or {bv}
+        id: Vec<String>,
+        #[command(flatten)]
+        pages: PageArgs,
+        /// merge history snapshots of these dates like `2024-04-18`
+        #[arg(long, value_delimiter = ',', value_parser = parse_date, conflicts_with = "all_history")]
+        date: Vec<String>,
+        /// merge every history snapshot since the video was published
+        #[arg(long)]
+        all_history: bool,
+        /// with `--all-history`, skip snapshots before this date like `2024-04-18`
+        #[arg(long, value_parser = parse_date, requires = "all_history")]
+        since: Option<String>,
+        /// write every field to `.danmaku.json` instead of rendering `.danmaku.ass`
+        #[arg(long)]
+        json: bool,
+    },
+    /// search bilibili and list the results as a table
+    Search {
+        /// words to search, joined by spaces
+        #[arg(required = true)]
+        keyword: Vec<String>,
+        /// `video`, `user`, `bangumi`, `live` or `article`
+        #[arg(long = "type", default_value = "video", value_parser = search::parse_type)]
+        search_type: SearchType,
+        /// `rank`, `click`, `pubdate`, `danmaku`, `favorite`, `comment`, `fans` or `level`
+        #[arg(long, value_parser = search::parse_order)]
+        order: Option<SearchOrder>,
+        /// video minutes, `0-10`, `10-30`, `30-60` or `60-`
+        #[arg(long, value_parser = search::parse_duration)]
+        duration: Option<SearchDuration>,
+        /// partition id of videos
+        #[arg(long)]
+        tid: Option<u32>,
+        /// results to list, pages are fetched as needed
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+        /// print only ids, one per line, for piping like `dc search -q .. | xargs dc bv`
+        #[arg(short, long)]
+        quiet: bool,
+        /// download listed videos by row like `1,3-5`
+        #[arg(long, value_parser = parse_pages)]
+        download: Option<Pages>,
+    },
+    /// download every video a user submitted into `{name}-{mid}/`, downloaded ones are skipped
+    User {
+        /// the number in `space.bilibili.com/{mid}`
+        mid: u64,
+        #[command(flatten)]
+        pages: PageArgs,
+        /// `pubdate`, `click` or `favorite`, newest first by default
+        #[arg(long, value_parser = parse_user_order)]
+        order: Option<UserVideoOrder>,
+        /// only videos with it in the title
+        #[arg(long)]
+        keyword: Option<String>,
+        /// videos to download in order, all of them by default
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// login by scanning a qrcode with the bilibili app
+    Login,
+}
+
+#[derive(Args, Debug, Clone)]
+struct PageArgs {
+    /// parts of multi-part videos like `1,3-5`, only P1 by default
+    #[arg(long, value_parser = parse_pages, conflicts_with = "all_pages")]
+    pages: Option<Pages>,
+    /// every part of multi-part videos
+    #[arg(long)]
+    all_pages: bool,
+}
+
+impl PageArgs {
+    fn contains(&self, page: u32) -> bool {
+        match (&self.pages, self.all_pages) {
+            (_, true) => true,
+            (Some(pages), _) => pages.0.iter().any(|r| r.contains(&page)),
+            (None, false) => page == 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Pages(Vec<std::ops::RangeInclusive<u32>>);
+
+/// where `downloads` writes videos
+#[derive(Debug, Clone, Default)]
+struct Target {
+    /// the current directory when empty
+    folder: std::path::PathBuf,
+    /// leave videos finished by an earlier run alone
+    skip_existing: bool,
 }
 
 use bili::{prelude::*, *};
+use futures::{StreamExt, TryStreamExt};
 use tokio::task::JoinSet;
 
 fn main() {
@@ -46,21 +216,315 @@ fn main() {
 }
 
 async fn anyhow_downolad(cli: Cli) -> anyhow::Result<()> {
-    let s = std::sync::Arc::new(Service::new());
+    let mut builder = Service::builder();
+    let store = credential_store();
+    let saved = match &cli.cookie {
+        Some(cookie) => {
+            let credential = if std::path::Path::new(cookie).is_file() {
+                Credential::from_file(cookie)?
+            } else {
+                Credential::from_cookie_str(cookie)?
+            };
+            builder = builder.credential(credential);
+            false
+        }
+        None => match store.as_ref().map(|s| s.load()).transpose()?.flatten() {
+            Some(credential) => {
+                builder = builder.credential(credential);
+                true
+            }
+            None => false,
+        },
+    };
+    if let Some(proxy) = proxy_config(&cli)? {
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &cli.record {
+        builder = builder.cassette(network_tools::Cassette::record(path));
+    }
+    if let Some(path) = &cli.replay {
+        builder = builder.cassette(network_tools::Cassette::replay(path).await?);
+    }
+    let s = std::sync::Arc::new(
+        builder
+            .rate_limiter(RateLimiter::new(cli.limit_rate))
+            .build()?,
+    );
+    // only the saved credential is rotated, `--cookie` is left to its owner
+    if let (true, Some(store)) = (saved, &store) {
+        if let Err(err) = s.refresh_if_needed(store).await {
+            eprintln!("refresh credential failed: {}", err);
+        }
+    }
     match cli.command {
-        Commands::AV { aid } => {
-            downloads(s, aid.iter().map(|id| VideoId::AID(*id)).collect()).await
+        Commands::AV { aid, pages } => {
+            let ids = aid.iter().map(|id| VideoId::AID(*id)).collect();
+            downloads(s, ids, pages, cli.sidecars, Target::default()).await
+        }
+        Commands::BV { bvid, pages } => {
+            let ids = bvid.iter().map(|id| VideoId::BVID(id.clone())).collect();
+            downloads(s, ids, pages, cli.sidecars, Target::default()).await
         }
-        Commands::BV { bvid } => {
-            downloads(s, bvid.iter().map(|id| VideoId::BVID(id.clone())).collect()).await
+        Commands::Season { id } => download_season(s, id, cli.sidecars).await,
+        Commands::Danmaku {
+            id,
+            pages,
+            date,
+            all_history,
+            since,
+            json,
+        } => {
+            let history = if all_history {
+                History::All(since)
+            } else if date.is_empty() {
+                History::None
+            } else {
+                History::Dates(date)
+            };
+            download_danmaku(s, id, pages, history, json).await
+        }
+        Commands::Search {
+            keyword,
+            search_type,
+            order,
+            duration,
+            tid,
+            limit,
+            quiet,
+            download,
+        } => {
+            let mut param = SearchParamBuilder::default();
+            param.keyword(keyword.join(" ")).search_type(search_type);
+            if let Some(order) = order {
+                param.order(order);
+            }
+            if let Some(duration) = duration {
+                param.duration(duration);
+            }
+            if let Some(tid) = tid {
+                param.tid(tid);
+            }
+            let results = s
+                .search_stream(&param.build()?)
+                .take(limit)
+                .try_collect::<Vec<_>>()
+                .await?;
+            if quiet {
+                results.iter().for_each(|r| println!("{}", search::id(r)));
+            } else {
+                print!("{}", search::table(&results));
+            }
+            let Some(rows) = download else {
+                return Ok(());
+            };
+            let ids = results
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| rows.0.iter().any(|r| r.contains(&(*i as u32 + 1))))
+                .map(|(_, r)| match r {
+                    SearchResult::Video(v) => Ok(VideoId::BVID(v.bvid().clone())),
+                    r => Err(anyhow!("{} is not a video", search::id(r))),
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let pages = PageArgs {
+                pages: None,
+                all_pages: false,
+            };
+            downloads(s, ids, pages, cli.sidecars, Target::default()).await
+        }
+        Commands::User {
+            mid,
+            pages,
+            order,
+            keyword,
+            limit,
+        } => {
+            let mut param = UserVideoParamBuilder::default();
+            param.mid(mid);
+            if let Some(order) = order {
+                param.order(order);
+            }
+            if let Some(keyword) = keyword {
+                param.keyword(keyword);
+            }
+            let videos = s
+                .user_videos_stream(&param.build()?)
+                .take(limit.unwrap_or(usize::MAX))
+                .try_collect::<Vec<_>>()
+                .await?;
+            let Some(first) = videos.first() else {
+                println!("No video of user {}", mid);
+                return Ok(());
+            };
+            let name = normalization_file_name(first.author().clone());
+            let folder = format!("{}-{}", name, mid);
+            tokio::fs::create_dir_all(&folder)
+                .await
+                .map_err(|e| anyhow!("create folder {} failed: {}", folder, e))?;
+            println!("{} videos of {} into {}", videos.len(), name, folder);
+            let ids = videos
+                .iter()
+                .map(|v| VideoId::BVID(v.bvid().clone()))
+                .collect();
+            let target = Target {
+                folder: folder.into(),
+                skip_existing: true,
+            };
+            downloads(s, ids, pages, cli.sidecars, target).await
+        }
+        Commands::Login => login(s).await,
+    }
+}
+
+/// `None` keeps the proxies from the environment
+fn proxy_config(cli: &Cli) -> anyhow::Result<Option<ProxyConfig>> {
+    if cli.proxy.is_none() && cli.api_proxy.is_none() && cli.cdn_proxy.is_none() {
+        return Ok(None);
+    }
+    let route = |proxy: &Option<String>| match proxy.as_deref() {
+        Some("direct") => Some(None),
+        Some(proxy) => Some(Some(proxy.to_owned())),
+        None => None,
+    };
+    let mut config = ProxyConfig::default().default_proxy(cli.proxy.as_deref())?;
+    if let Some(proxy) = route(&cli.api_proxy) {
+        config = config.route_all(consts::API_DOMAINS, proxy.as_deref())?;
+    }
+    if let Some(proxy) = route(&cli.cdn_proxy) {
+        config = config.route_all(consts::CDN_DOMAINS, proxy.as_deref())?;
+    }
+    Ok(Some(config))
+}
+
+/// `500K`, `2M`, `1.5M` or plain bytes, 1K is 1024 bytes
+fn parse_rate(s: &str) -> std::result::Result<u64, String> {
+    let s = s.trim();
+    let (num, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_uppercase()),
+        _ => (s, 'B'),
+    };
+    let scale: u64 = match unit {
+        'B' => 1,
+        'K' => 1 << 10,
+        'M' => 1 << 20,
+        'G' => 1 << 30,
+        _ => return Err(format!("unknown unit {}", unit)),
+    };
+    let num = num
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("invalid rate {}: {}", s, e))?;
+    if num > 0.0 {
+        Ok((num * scale as f64) as u64)
+    } else {
+        Err("rate must be positive".to_owned())
+    }
+}
+
+/// `1,3-5`, pages are 1 based
+fn parse_pages(s: &str) -> std::result::Result<Pages, String> {
+    let page = |p: &str| match p.trim().parse::<u32>() {
+        Ok(0) | Err(_) => Err(format!("invalid page {}", p)),
+        Ok(p) => Ok(p),
+    };
+    s.split(',')
+        .map(|r| match r.split_once('-') {
+            Some((start, end)) => match (page(start)?, page(end)?) {
+                (start, end) if start <= end => Ok(start..=end),
+                _ => Err(format!("invalid range {}", r)),
+            },
+            None => page(r).map(|p| p..=p),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map(Pages)
+}
+
+/// `srt`, `vtt` or `ass`
+fn parse_sub_format(s: &str) -> std::result::Result<SubtitleFormat, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "srt" => Ok(SubtitleFormat::Srt),
+        "vtt" => Ok(SubtitleFormat::Vtt),
+        "ass" => Ok(SubtitleFormat::Ass),
+        _ => Err(format!("unknown subtitle format: {}", s)),
+    }
+}
+
+/// `YYYY-MM-DD`
+fn parse_date(s: &str) -> std::result::Result<String, String> {
+    let invalid = || format!("invalid date {}, expect YYYY-MM-DD", s);
+    let parts = s.split('-').collect::<Vec<_>>();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let num = |v: &str, len: usize| {
+        if v.len() == len && v.bytes().all(|b| b.is_ascii_digit()) {
+            v.parse::<u32>().map_err(|_| invalid())
+        } else {
+            Err(invalid())
+        }
+    };
+    num(year, 4)?;
+    match (num(month, 2)?, num(day, 2)?) {
+        (1..=12, 1..=31) => Ok(s.to_owned()),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_user_order(s: &str) -> std::result::Result<UserVideoOrder, String> {
+    match s {
+        "pubdate" => Ok(UserVideoOrder::PubDate),
+        "click" => Ok(UserVideoOrder::Click),
+        "favorite" => Ok(UserVideoOrder::Favorite),
+        _ => Err(format!(
+            "unknown order {}, expect pubdate, click or favorite",
+            s
+        )),
+    }
+}
+
+/// `{config_dir}/dc/credential.json`
+fn credential_store() -> Option<FileCredentialStore> {
+    dirs::config_dir().map(|p| FileCredentialStore::new(p.join("dc").join("credential.json")))
+}
+
+async fn login(s: std::sync::Arc<Service<'static>>) -> anyhow::Result<()> {
+    use qrcode::render::unicode::Dense1x2;
+
+    let qrcode = s.generate_qrcode().await?;
+    let image = qrcode::QrCode::new(qrcode.url().as_bytes())?
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build();
+    println!("{}", image);
+    println!("Scan with the bilibili app: {}", qrcode.url());
+
+    let mut scanned = false;
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        match s.poll_qrcode(qrcode.qrcode_key()).await? {
+            QrLoginState::Waiting => {}
+            QrLoginState::Scanned => {
+                if !scanned {
+                    println!("Scanned, confirm login in the app");
+                    scanned = true;
+                }
+            }
+            QrLoginState::Expired => return Err(anyhow!("qrcode expired, run login again")),
+            QrLoginState::Confirmed(credential) => {
+                let store = credential_store().ok_or(anyhow!("no config dir"))?;
+                store.save(&credential)?;
+                println!("Login success, credential saved to {}", store.path().display());
+                return Ok(());
+            }
         }
-        Commands::Season { id } => download_season(s, id).await,
     }
 }
 
 async fn download_season(
     s: std::sync::Arc<Service<'static>>,
     ids: Vec<String>,
+    sidecars: Sidecars,
 ) -> anyhow::Result<()> {
     let ids = ids
         .iter()
@@ -71,7 +535,7 @@ async fn download_season(
         .collect::<Vec<_>>();
     let mut fg: JoinSet<anyhow::Result<tokio::fs::File>> = tokio::task::JoinSet::new();
     let sem = std::sync::Arc::new(tokio::sync::Semaphore::new(3));
-    let p = indicatif::MultiProgress::new();
+    let p = Progress::new();
     for id in ids {
         let s = s.clone();
         let res = s.season_id(&id).await?.ok_or(anyhow!("this not season"));
@@ -85,8 +549,22 @@ async fn download_season(
                         tokio::fs::create_dir_all(folder_path).await.map_err(|e| {
                             anyhow::anyhow!("create folder failed: {}", e.to_string())
                         })?;
+                        if sidecars.write_nfo {
+                            let path = std::path::Path::new(folder_path).join("tvshow.nfo");
+                            if let Err(err) =
+                                tokio::fs::write(&path, nfo::tvshow(&season_list)).await
+                            {
+                                println!("Write {} failed: {}", path.display(), err);
+                            }
+                        }
+                        if sidecars.write_thumbnail && !season_list.cover_url().is_empty() {
+                            if let Some(cover) = fetch_cover(&s, season_list.cover_url()).await {
+                                let path = std::path::Path::new(folder_path).join("folder.jpg");
+                                write_file(&path, cover).await;
+                            }
+                        }
 
-                        for section in season_list.sections().into_iter() {
+                        for (index, section) in season_list.sections().iter().enumerate() {
                             // step2: create file
 
                             let music_title = {
@@ -131,16 +609,56 @@ async fn download_season(
                             let s = s.clone();
                             let section = section.clone();
                             let p = p.clone();
+                            let sidecars = sidecars.clone();
                             fg.spawn(async move {
                                 let _permit = permit;
+                                let id = VideoId::BVID(section.bvid().to_owned());
+                                let detail = if sidecars.needs_detail() {
+                                    s.get_video_detail(&id)
+                                        .await
+                                        .map_err(|err| {
+                                            println!("Get metadata of {} failed: {}", id, err)
+                                        })
+                                        .ok()
+                                } else {
+                                    None
+                                };
+                                let cover = match &detail {
+                                    Some(detail) if sidecars.needs_cover() => {
+                                        fetch_cover(&s, detail.pic_url()).await
+                                    }
+                                    _ => None,
+                                };
                                 download_writer(
-                                    s,
+                                    s.clone(),
                                     &mut f,
-                                    VideoId::BVID(section.bvid().to_owned()),
+                                    &file_path,
+                                    id,
                                     *section.cid(),
                                     p,
+                                    embedded_cover(&sidecars, &cover),
                                 )
                                 .await?;
+                                if let Some(detail) = detail {
+                                    let nfo = nfo::episode(&detail, index + 1);
+                                    write_sidecars(&sidecars, &file_path, &detail, nfo, cover)
+                                        .await;
+                                }
+                                let id = VideoId::BVID(section.bvid().to_owned());
+                                write_subtitles(&s, &sidecars, &file_path, &id, *section.cid())
+                                    .await;
+                                if sidecars.write_danmaku {
+                                    let page = s.get_pages(&id).await.map(|pages| {
+                                        pages.into_iter().find(|p| p.cid() == section.cid())
+                                    });
+                                    match page {
+                                        Ok(Some(page)) => {
+                                            write_danmaku(&s, &file_path, &page).await
+                                        }
+                                        Ok(None) => println!("No page {} in {}", section.cid(), id),
+                                        Err(err) => println!("Get pages of {} failed: {}", id, err),
+                                    }
+                                }
                                 Ok(f)
                             });
                         }
@@ -148,87 +666,525 @@ async fn download_season(
                     Err(err) => println!("Get season list for id: {} failed: {}", id, err,),
                 };
             }
-            Err(err) => println!("Err: {}", err.to_string()),
+            Err(err) => println!("Err: {}", err),
         };
     }
     while let Some(f) = fg.join_next().await {
         f??.sync_all().await?;
     }
+    p.total.finish();
     Ok(())
 }
 
-async fn downloads(s: std::sync::Arc<Service<'static>>, ids: Vec<VideoId>) -> anyhow::Result<()> {
+async fn downloads(
+    s: std::sync::Arc<Service<'static>>,
+    ids: Vec<VideoId>,
+    selection: PageArgs,
+    sidecars: Sidecars,
+    target: Target,
+) -> anyhow::Result<()> {
     let mut fg: JoinSet<anyhow::Result<()>> = tokio::task::JoinSet::new();
-    let p = indicatif::MultiProgress::new();
+    let p = Progress::new();
     let sem = std::sync::Arc::new(tokio::sync::Semaphore::new(3));
+    // the other videos are still downloaded, the command fails at the end
+    let mut failed = vec![];
     for id in ids {
-        let s = s.clone();
-        let p = p.clone();
-        let permit = std::sync::Arc::clone(&sem).acquire_owned().await?;
-        fg.spawn(async move {
-            let _permit = permit;
-            let basic_info = s.get_basic_info(&id).await?;
-
-            let music_title = {
-                let res = s.get_music_info(&(id.clone(), *basic_info.cid())).await;
-                match res {
-                    Ok(res) => res.title().clone(),
-                    Err(_) => basic_info.title().clone(),
+        let pages = match s.get_pages(&id).await {
+            Ok(pages) => pages,
+            Err(err) => {
+                println!("Get pages of {} failed: {}", id, err);
+                failed.push(id.to_string());
+                continue;
+            }
+        };
+        let multi_part = pages.len() > 1;
+        let selected = pages
+            .into_iter()
+            .filter(|page| selection.contains(*page.page()))
+            .collect::<Vec<_>>();
+        if selected.is_empty() {
+            println!("No selected page in {}", id);
+        }
+        let detail = if sidecars.needs_detail() {
+            s.get_video_detail(&id)
+                .await
+                .map_err(|err| println!("Get metadata of {} failed: {}", id, err))
+                .ok()
+        } else {
+            None
+        };
+        // every page shares the cover of the video
+        let cover = match &detail {
+            Some(detail) if sidecars.needs_cover() => fetch_cover(&s, detail.pic_url()).await,
+            _ => None,
+        };
+        for page in selected {
+            let s = s.clone();
+            let p = p.clone();
+            let id = id.clone();
+            let detail = detail.clone();
+            let cover = cover.clone();
+            let sidecars = sidecars.clone();
+            let target = target.clone();
+            let permit = std::sync::Arc::clone(&sem).acquire_owned().await?;
+            fg.spawn(async move {
+                let _permit = permit;
+                // titles may change, the id is what names a download
+                let suffix = if multi_part {
+                    format!("-{}-p{}.mp4", id, page.page())
+                } else {
+                    format!("-{}.mp4", id)
+                };
+                if target.skip_existing {
+                    if let Some(path) = downloaded(&target.folder, &suffix) {
+                        println!("Skip {}, already downloaded", path.display());
+                        return Ok(());
+                    }
                 }
-            };
-
-            let mut file = tokio::fs::File::create(format!(
-                "{}-{}.mp4",
-                normalization_file_name(music_title),
-                id
-            ))
-            .await?;
-            download_writer(s, &mut file, id, *basic_info.cid(), p).await?;
-            file.sync_all().await?;
-            Ok(())
-        });
+                // a part is named by itself, the music is the whole video's
+                let title = if multi_part {
+                    page.part().clone()
+                } else {
+                    match s.get_music_info(&(id.clone(), *page.cid())).await {
+                        Ok(res) => res.title().clone(),
+                        Err(_) => page.part().clone(),
+                    }
+                };
+                let file_name = format!("{}{}", normalization_file_name(title), suffix);
+                let file_path = target.folder.join(file_name);
+                let mut file = tokio::fs::File::create(&file_path).await?;
+                let embedded = embedded_cover(&sidecars, &cover);
+                download_writer(
+                    s.clone(),
+                    &mut file,
+                    &file_path,
+                    id.clone(),
+                    *page.cid(),
+                    p,
+                    embedded,
+                )
+                .await?;
+                file.sync_all().await?;
+                if let Some(detail) = detail {
+                    let nfo = nfo::movie(&detail, multi_part.then_some(&page));
+                    write_sidecars(&sidecars, &file_path, &detail, nfo, cover).await;
+                }
+                write_subtitles(&s, &sidecars, &file_path, &id, *page.cid()).await;
+                if sidecars.write_danmaku {
+                    write_danmaku(&s, &file_path, &page).await;
+                }
+                Ok(())
+            });
+        }
     }
     while let Some(f) = fg.join_next().await {
         f??;
     }
+    p.total.finish();
+    if !failed.is_empty() {
+        return Err(anyhow!("get pages of {} failed", failed.join(", ")));
+    }
     Ok(())
 }
 
+/// `{video}.info.json`, `{video}.nfo` and `{video}-poster.jpg` as asked, failures are only reported
+async fn write_sidecars(
+    sidecars: &Sidecars,
+    file_path: &std::path::Path,
+    detail: &VideoDetail,
+    nfo: String,
+    cover: Option<Vec<u8>>,
+) {
+    let mut files = vec![];
+    if sidecars.write_info_json {
+        match serde_json::to_vec_pretty(detail) {
+            Ok(json) => files.push((file_path.with_extension("info.json"), json)),
+            Err(err) => println!("Serialize metadata of {} failed: {}", detail.bvid(), err),
+        }
+    }
+    if sidecars.write_nfo {
+        files.push((file_path.with_extension("nfo"), nfo.into_bytes()));
+    }
+    if let Some(cover) = cover.filter(|_| sidecars.write_thumbnail) {
+        // kodi and jellyfin take `{video}-poster.jpg` as artwork, a bare `cover.jpg`
+        // would be shared by every video of the folder
+        let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+        let poster = file_path.with_file_name(format!("{}-poster.jpg", stem));
+        files.push((poster, cover));
+    }
+    for (path, content) in files {
+        write_file(&path, content).await;
+    }
+}
+
+/// subtitles asked by `--sub-langs`, failures are only reported
+async fn write_subtitles(
+    s: &Service<'static>,
+    sidecars: &Sidecars,
+    file_path: &std::path::Path,
+    id: &VideoId,
+    cid: u64,
+) {
+    let Some(langs) = &sidecars.sub_langs else {
+        return;
+    };
+    let tracks = match s.get_subtitle_tracks(id, cid).await {
+        Ok(tracks) => tracks,
+        Err(err) => {
+            println!("Get subtitles of {} failed: {}", id, err);
+            return;
+        }
+    };
+    let all = langs.iter().any(|l| l == "all");
+    let missing = langs
+        .iter()
+        .filter(|l| !all && !tracks.iter().any(|t| t.lang() == *l));
+    for lang in missing {
+        println!("No {} subtitle in {}", lang, id);
+    }
+    for track in tracks.iter().filter(|t| all || langs.contains(t.lang())) {
+        match s.get_subtitle(track).await {
+            Ok(subtitle) => {
+                let ext = format!("{}.{}", track.lang(), sidecars.sub_format.extension());
+                let content = subtitle.convert(sidecars.sub_format);
+                write_file(&file_path.with_extension(ext), content.into_bytes()).await;
+            }
+            Err(err) => println!("Get {} subtitle of {} failed: {}", track.lang(), id, err),
+        }
+    }
+}
+
+/// `{video}.danmaku.ass` sized to the page, failures are only reported
+async fn write_danmaku(s: &Service<'static>, file_path: &std::path::Path, page: &VideoPage) {
+    let danmakus = match s.get_danmaku(*page.cid(), *page.duration()).await {
+        Ok(danmakus) => danmakus,
+        Err(err) => {
+            println!("Get danmaku of {} failed: {}", page.cid(), err);
+            return;
+        }
+    };
+    let ass = render_ass(&danmakus, &danmaku_options(page));
+    write_file(&file_path.with_extension("danmaku.ass"), ass.into_bytes()).await;
+}
+
+fn danmaku_options(page: &VideoPage) -> DanmakuAssOptions {
+    let mut options = DanmakuAssOptionsBuilder::default();
+    // keep the aspect ratio of the video on a 1080 lines canvas
+    if let Some(d) = page.dimension() {
+        let (w, h) = match d.rotate() {
+            1 => (d.height(), d.width()),
+            _ => (d.width(), d.height()),
+        };
+        if *w > 0 && *h > 0 {
+            options.width(1080 * w / h).height(1080);
+        }
+    }
+    options.build().expect("danmaku options have defaults")
+}
+
+/// which danmaku `dc danmaku` merges
+enum History {
+    /// the current ones
+    None,
+    Dates(Vec<String>),
+    /// every date with a snapshot since published, or since the given date
+    All(Option<String>),
+}
+
+async fn download_danmaku(
+    s: std::sync::Arc<Service<'static>>,
+    ids: Vec<String>,
+    selection: PageArgs,
+    history: History,
+    json: bool,
+) -> anyhow::Result<()> {
+    for id in ids {
+        let id = match id.parse::<u64>() {
+            Ok(id) => VideoId::AID(id),
+            Err(_) => VideoId::BVID(id),
+        };
+        let pages = s.get_pages(&id).await?;
+        let multi_part = pages.len() > 1;
+        // months to look for snapshots, the same for every page
+        let months = match &history {
+            History::All(since) => {
+                let detail = s.get_video_detail(&id).await?;
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)?
+                    .as_secs() as i64;
+                let pubdate = nfo::date(*detail.pubdate());
+                let from = since.as_ref().map_or(&pubdate, |since| since.max(&pubdate));
+                months(&from[..7], &nfo::date(now)[..7])
+            }
+            _ => vec![],
+        };
+        for page in pages.iter().filter(|p| selection.contains(*p.page())) {
+            let cid = *page.cid();
+            let danmakus = match &history {
+                History::None => s.get_danmaku(cid, *page.duration()).await?,
+                History::Dates(dates) => danmaku_history(&s, cid, dates).await?,
+                History::All(since) => {
+                    let mut dates = vec![];
+                    for month in &months {
+                        tokio::time::sleep(HISTORY_PAUSE).await;
+                        dates.extend(s.get_danmaku_history_dates(cid, month).await?);
+                    }
+                    if let Some(since) = since {
+                        dates.retain(|date| date >= since);
+                    }
+                    danmaku_history(&s, cid, &dates).await?
+                }
+            };
+            let stem = if multi_part {
+                format!("{}-p{}", id, page.page())
+            } else {
+                id.to_string()
+            };
+            let (path, content) = if json {
+                (
+                    format!("{}.danmaku.json", stem),
+                    serde_json::to_vec_pretty(&danmakus)?,
+                )
+            } else {
+                (
+                    format!("{}.danmaku.ass", stem),
+                    render_ass(&danmakus, &danmaku_options(page)).into_bytes(),
+                )
+            };
+            tokio::fs::write(&path, content).await?;
+            println!("{} danmaku saved to {}", danmakus.len(), path);
+        }
+    }
+    Ok(())
+}
+
+/// snapshots of `dates` merged, a danmaku in many snapshots is kept once
+async fn danmaku_history(
+    s: &Service<'static>,
+    cid: u64,
+    dates: &[String],
+) -> anyhow::Result<Vec<Danmaku>> {
+    let mut snapshots = vec![];
+    for date in dates {
+        tokio::time::sleep(HISTORY_PAUSE).await;
+        snapshots.extend(s.get_danmaku_history(cid, date).await?);
+    }
+    Ok(Danmaku::merge(snapshots))
+}
+
+/// `YYYY-MM` from `from` to `to` inclusive
+fn months(from: &str, to: &str) -> Vec<String> {
+    let parse = |m: &str| -> Option<(u32, u32)> {
+        let (y, m) = m.split_once('-')?;
+        Some((y.parse().ok()?, m.parse().ok()?))
+    };
+    let (Some(mut cur), Some(end)) = (parse(from), parse(to)) else {
+        return vec![];
+    };
+    let mut months = vec![];
+    while cur <= end {
+        months.push(format!("{:04}-{:02}", cur.0, cur.1));
+        cur = match cur.1 {
+            12 => (cur.0 + 1, 1),
+            m => (cur.0, m + 1),
+        };
+    }
+    months
+}
+
+async fn write_file(path: &std::path::Path, content: Vec<u8>) {
+    if let Err(err) = tokio::fs::write(path, content).await {
+        println!("Write {} failed: {}", path.display(), err);
+    }
+}
+
+/// the cover as jpeg, which players read best, failures are only reported
+async fn fetch_cover(s: &Service<'static>, url: &str) -> Option<Vec<u8>> {
+    let param = ImageParamBuilder::default()
+        .format(ImageFormat::Jpg)
+        .build()
+        .expect("image param has defaults");
+    match s.get_image(url, &param).await {
+        Ok(image) => Some(image.into_data()),
+        Err(err) => {
+            println!("Get cover {} failed: {}", url, err);
+            None
+        }
+    }
+}
+
+fn embedded_cover(sidecars: &Sidecars, cover: &Option<Vec<u8>>) -> Option<mp4_mux::Cover> {
+    match cover {
+        Some(cover) if sidecars.embed_thumbnail => {
+            let embedded = mp4_mux::Cover::new(cover.clone());
+            if embedded.is_none() {
+                println!("Cover is neither jpeg nor png, not embedded");
+            }
+            embedded
+        }
+        _ => None,
+    }
+}
+
+/// a bar per file and one for everything
+#[derive(Clone)]
+struct Progress {
+    multi: indicatif::MultiProgress,
+    total: indicatif::ProgressBar,
+}
+
+impl Progress {
+    const TEMPLATE: &'static str = "[{elapsed_precise}] {bar:40.cyan/blue} {bytes:>10}/{total_bytes:10} {bytes_per_sec:>12} {eta:>4} {msg}";
+
+    fn new() -> Self {
+        let multi = indicatif::MultiProgress::new();
+        let total = multi.add(indicatif::ProgressBar::new(0).with_message("total"));
+        total.set_style(Self::style());
+        Self { multi, total }
+    }
+
+    fn style() -> indicatif::ProgressStyle {
+        indicatif::ProgressStyle::with_template(Self::TEMPLATE)
+            .unwrap()
+            .progress_chars("##-")
+    }
+
+    fn add(&self, size: u64, msg: String) -> indicatif::ProgressBar {
+        let pb = self
+            .multi
+            .insert_before(&self.total, indicatif::ProgressBar::new(size).with_message(msg));
+        pb.set_style(Self::style());
+        self.total.inc_length(size);
+        pb
+    }
+
+    /// feed `DownloadEvent`s of a file into its bar and the total
+    fn report(
+        &self,
+        pb: indicatif::ProgressBar,
+    ) -> (
+        tokio::sync::mpsc::UnboundedSender<DownloadEvent>,
+        tokio::task::JoinHandle<()>,
+    ) {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let total = self.total.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    DownloadEvent::Started { finished: n, .. } | DownloadEvent::Bytes(n) => {
+                        pb.inc(n);
+                        total.inc(n);
+                    }
+                    _ => {}
+                }
+            }
+        });
+        (tx, task)
+    }
+}
+
 async fn download_writer(
     s: std::sync::Arc<Service<'static>>,
     f: &mut tokio::fs::File,
+    file_path: &std::path::Path,
     id: VideoId,
     cid: u64,
-    p: indicatif::MultiProgress,
+    p: Progress,
+    cover: Option<mp4_mux::Cover>,
 ) -> anyhow::Result<()> {
-    let sty = indicatif::ProgressStyle::with_template(
-        "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-    )
-    .unwrap()
-    .progress_chars("##-");
-    let download_info = s
-        .get_download_info(&GetDownloadInfoParam {
+    let dash_info = s
+        .get_dash_info(&GetDownloadInfoParam {
             id: id.clone(),
             cid,
             clarity: Clarity::Low,
         })
         .await?;
-    let pb = p.add(indicatif::ProgressBar::new(*download_info.size()).with_message(id.to_string()));
-    pb.set_style(sty);
-    pb.inc(1);
-    s.download(
-        &DownloadParam {
-            info: download_info,
-            chunk_size: Some(CHUNK_SIZE),
-            conn_pool: Some(CONN_POOL_SIZE),
-        },
-        f,
-    )
-    .await?;
+    let selector = DashSelector::default();
+    let mut tracks = vec![];
+    let video = dash_info
+        .select_video(&selector)
+        .ok_or(anyhow!("no video track for {}", id))?;
+    tracks.push(s.get_track_info(video).await?);
+    if let Some(audio) = dash_info.select_audio(&selector) {
+        tracks.push(s.get_track_info(audio).await?);
+    }
+    let size = tracks.iter().map(|t| *t.size()).sum();
+    let pb = p.add(size, id.to_string());
+
+    // step1: download every dash track next to the target
+    let mut parts = vec![];
+    for (i, info) in tracks.into_iter().enumerate() {
+        let part_path = file_path.with_extension(format!("{}.m4s", i));
+        let journal = Journal::path_for(&part_path);
+        let mut part = tokio::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&part_path)
+            .await?;
+        // without a matching journal nothing on disk can be trusted
+        if Journal::load(&journal, *info.size(), CHUNK_SIZE)
+            .await
+            .is_fresh()
+        {
+            part.set_len(0).await?;
+        }
+        let (progress, report) = p.report(pb.clone());
+        s.download(
+            &DownloadParam {
+                info,
+                chunk_size: Some(CHUNK_SIZE),
+                conn_pool: Some(CONN_POOL_SIZE),
+                journal: Some(journal),
+                retry: None,
+                progress: Some(progress),
+            },
+            &mut part,
+        )
+        .await?;
+        report.await?;
+        part.sync_all().await?;
+        parts.push(part_path);
+    }
+
+    // step2: remux tracks into the target
+    f.set_len(0).await?;
+    let out = f.try_clone().await?.into_std().await;
+    let inputs = parts.clone();
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let mut inputs = inputs
+            .iter()
+            .map(|p| std::fs::File::open(p).map(std::io::BufReader::new))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let mut out = std::io::BufWriter::new(out);
+        mp4_mux::remux_with(&mut inputs, &mut out, &mp4_mux::Metadata { cover })?;
+        Ok(())
+    })
+    .await??;
+    for part in parts {
+        tokio::fs::remove_file(part).await?;
+    }
     pb.finish();
     Ok(())
 }
 
+/// a remuxed file of `folder` named `{title}{suffix}`, whatever the title was,
+/// `download_writer` removes the parts only after remuxing
+fn downloaded(folder: &std::path::Path, suffix: &str) -> Option<std::path::PathBuf> {
+    std::fs::read_dir(folder)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            let named = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with(suffix));
+            let remuxed = path.metadata().is_ok_and(|m| m.is_file() && m.len() > 0);
+            named && remuxed && !path.with_extension("0.m4s").exists()
+        })
+}
+
 fn normalization_file_name(s: String) -> String {
     let s: Vec<u8> = s
         .trim()
@@ -237,3 +1193,85 @@ fn normalization_file_name(s: String) -> String {
         .collect();
     String::from_utf8_lossy(&s).into_owned()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pages() {
+        let pages = parse_pages("1,3-5, 7").unwrap();
+        assert_eq!(pages.0, vec![1..=1, 3..=5, 7..=7]);
+        assert_eq!(parse_pages("2-2").unwrap().0, vec![2..=2]);
+        for invalid in ["0", "0-2", "5-3", "3-", "-3", "a", "1,,2", ""] {
+            assert!(parse_pages(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_page_args() {
+        let args = |pages: Option<&str>, all_pages| PageArgs {
+            pages: pages.map(|p| parse_pages(p).unwrap()),
+            all_pages,
+        };
+        let first = args(None, false);
+        assert!(first.contains(1) && !first.contains(2));
+        let some = args(Some("2,4-5"), false);
+        let selected = (1..=6).filter(|p| some.contains(*p)).collect::<Vec<_>>();
+        assert_eq!(selected, vec![2, 4, 5]);
+        assert!((1..=100).all(|p| args(None, true).contains(p)));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2024-04-18").unwrap(), "2024-04-18");
+        for invalid in [
+            "2024-4-18",
+            "2024-00-18",
+            "2024-13-01",
+            "2024-04-00",
+            "2024-04-32",
+            "24-04-18",
+            "2024-04",
+            "2024-04-18-1",
+            "2024-0a-18",
+        ] {
+            assert!(parse_date(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_months() {
+        assert_eq!(months("2024-04", "2024-04"), vec!["2024-04"]);
+        assert_eq!(
+            months("2023-11", "2024-02"),
+            vec!["2023-11", "2023-12", "2024-01", "2024-02"]
+        );
+        assert!(months("2024-05", "2024-04").is_empty());
+        assert!(months("2024", "2024-04").is_empty());
+    }
+
+    #[test]
+    fn test_downloaded() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("dc-downloaded-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        // renamed since, still the same video
+        std::fs::write(dir.join("old title-BV1xx.mp4"), b"mp4")?;
+        std::fs::write(dir.join("a part-BV1yy-p2.mp4"), b"mp4")?;
+        // interrupted before remuxing
+        std::fs::write(dir.join("part-BV1zz.mp4"), b"")?;
+        std::fs::write(dir.join("remux-BV1ww.mp4"), b"mp4")?;
+        std::fs::write(dir.join("remux-BV1ww.0.m4s"), b"m4s")?;
+        assert_eq!(
+            downloaded(&dir, "-BV1xx.mp4"),
+            Some(dir.join("old title-BV1xx.mp4"))
+        );
+        assert!(downloaded(&dir, "-BV1yy-p2.mp4").is_some());
+        assert!(downloaded(&dir, "-BV1yy-p1.mp4").is_none());
+        assert!(downloaded(&dir, "-BV1yy.mp4").is_none());
+        assert!(downloaded(&dir, "-BV1zz.mp4").is_none());
+        assert!(downloaded(&dir, "-BV1ww.mp4").is_none());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! kodi/jellyfin `.nfo` files
+
+use bili::{SeasonList, VideoDetail, VideoPage};
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#;
+
+/// a standalone video, or one `part` of a multi-part video
+pub fn movie(detail: &VideoDetail, part: Option<&VideoPage>) -> String {
+    let fields = match part {
+        Some(part) => video_fields(
+            detail,
+            &format!("{} - P{} {}", detail.title(), part.page(), part.part()),
+            *part.duration(),
+        ),
+        None => video_fields(detail, detail.title(), *detail.duration()),
+    };
+    document("movie", &fields)
+}
+
+/// a video of a season, `episode` is 1 based
+pub fn episode(detail: &VideoDetail, episode: usize) -> String {
+    let mut fields = video_fields(detail, detail.title(), *detail.duration());
+    fields.push(element("season", "1"));
+    fields.push(element("episode", &episode.to_string()));
+    document("episodedetails", &fields)
+}
+
+/// `tvshow.nfo` of a season folder
+pub fn tvshow(season: &SeasonList) -> String {
+    let mut fields = vec![
+        element("title", season.season_name()),
+        element("plot", season.intro()),
+        element("studio", season.owner().name()),
+    ];
+    if !season.cover_url().is_empty() {
+        fields.push(format!(
+            r#"<thumb aspect="poster">{}</thumb>"#,
+            escape(season.cover_url())
+        ));
+    }
+    fields.push(format!(
+        r#"<uniqueid type="bilibili_season" default="true">{}</uniqueid>"#,
+        season.season_id()
+    ));
+    document("tvshow", &fields)
+}
+
+/// `duration` in seconds
+fn video_fields(detail: &VideoDetail, title: &str, duration: u64) -> Vec<String> {
+    let premiered = date(*detail.pubdate());
+    let mut fields = vec![
+        element("title", title),
+        element("plot", detail.description()),
+        element("runtime", &duration.div_ceil(60).to_string()),
+        element("premiered", &premiered),
+        element("year", &premiered[..4]),
+        element("studio", detail.owner().name()),
+        element("director", detail.owner().name()),
+        element("genre", detail.tname()),
+    ];
+    fields.extend(detail.tags().iter().map(|t| element("tag", t.tag_name())));
+    fields.push(format!(
+        r#"<thumb aspect="poster">{}</thumb>"#,
+        escape(detail.pic_url())
+    ));
+    fields.push(format!(
+        r#"<uniqueid type="bilibili" default="true">{}</uniqueid>"#,
+        escape(detail.bvid())
+    ));
+    fields
+}
+
+fn document(root: &str, fields: &[String]) -> String {
+    let mut doc = format!("{}\n<{}>\n", HEADER, root);
+    for field in fields {
+        doc.push_str("  ");
+        doc.push_str(field);
+        doc.push('\n');
+    }
+    doc.push_str(&format!("</{}>\n", root));
+    doc
+}
+
+fn element(name: &str, text: &str) -> String {
+    format!("<{0}>{1}</{0}>", name, escape(text))
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `YYYY-MM-DD` of unix seconds in beijing time, where bilibili publishes
+pub fn date(ts: i64) -> String {
+    // days to civil date, from howard hinnant's `civil_from_days`
+    let z = (ts + 8 * 3600).div_euclid(86400) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        // 2024-04-18 00:00 in beijing is still the 17th in utc
+        assert_eq!(date(1713369600), "2024-04-18");
+        assert_eq!(date(1713369600 - 1), "2024-04-17");
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(-8 * 3600 - 1), "1969-12-31");
+        // leap days, 2100 is not a leap year
+        assert_eq!(date(1709136000), "2024-02-29");
+        assert_eq!(date(951753600), "2000-02-29");
+        assert_eq!(date(4107513600 - 1), "2100-02-28");
+        assert_eq!(date(1703952000 + 86400), "2024-01-01");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            element("title", r#"<a & "b's">"#),
+            "<title>&lt;a &amp; &quot;b&apos;s&quot;&gt;</title>"
+        );
+    }
+}
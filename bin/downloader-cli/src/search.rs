@@ -0,0 +1,143 @@
+//! `dc search` arguments and its table
+
+use bili::{SearchDuration, SearchOrder, SearchResult, SearchType};
+
+pub fn parse_type(s: &str) -> Result<SearchType, String> {
+    match s {
+        "video" => Ok(SearchType::Video),
+        "user" => Ok(SearchType::User),
+        "bangumi" => Ok(SearchType::Bangumi),
+        "live" => Ok(SearchType::LiveRoom),
+        "article" => Ok(SearchType::Article),
+        _ => Err(format!(
+            "unknown type {}, expect video, user, bangumi, live or article",
+            s
+        )),
+    }
+}
+
+pub fn parse_order(s: &str) -> Result<SearchOrder, String> {
+    match s {
+        "rank" => Ok(SearchOrder::TotalRank),
+        "click" => Ok(SearchOrder::Click),
+        "pubdate" => Ok(SearchOrder::PubDate),
+        "danmaku" => Ok(SearchOrder::Danmaku),
+        "favorite" => Ok(SearchOrder::Favorite),
+        "comment" => Ok(SearchOrder::Comment),
+        "fans" => Ok(SearchOrder::Fans),
+        "level" => Ok(SearchOrder::Level),
+        _ => Err(format!(
+            "unknown order {}, expect rank, click, pubdate, danmaku, favorite, comment, fans or level",
+            s
+        )),
+    }
+}
+
+/// minutes like `0-10`, `10-30`, `30-60` or `60-`
+pub fn parse_duration(s: &str) -> Result<SearchDuration, String> {
+    match s {
+        "0-10" => Ok(SearchDuration::Under10Min),
+        "10-30" => Ok(SearchDuration::From10To30Min),
+        "30-60" => Ok(SearchDuration::From30To60Min),
+        "60-" => Ok(SearchDuration::Over60Min),
+        _ => Err(format!(
+            "unknown duration {}, expect 0-10, 10-30, 30-60 or 60-",
+            s
+        )),
+    }
+}
+
+/// what other commands take, e.g. the bvid of a video
+pub fn id(result: &SearchResult) -> String {
+    match result {
+        SearchResult::Video(v) => v.bvid().clone(),
+        SearchResult::User(u) => u.mid().to_string(),
+        SearchResult::Bangumi(b) => format!("ss{}", b.season_id()),
+        SearchResult::LiveRoom(r) => r.room_id().to_string(),
+        SearchResult::Article(a) => format!("cv{}", a.id()),
+    }
+}
+
+/// rows numbered from 1, which `--download` selects
+pub fn table(results: &[SearchResult]) -> String {
+    let rows = results
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let mut row = vec![(i + 1).to_string(), id(r)];
+            row.extend(match r {
+                SearchResult::Video(v) => vec![
+                    clock(*v.duration()),
+                    count(*v.play()),
+                    v.author().clone(),
+                    v.title().clone(),
+                ],
+                SearchResult::User(u) => vec![
+                    format!("lv{}", u.level()),
+                    count(*u.fans()),
+                    u.name().clone(),
+                    u.sign().clone(),
+                ],
+                SearchResult::Bangumi(b) => vec![
+                    format!("{}ep", b.ep_size()),
+                    b.score().map(|s| format!("{:.1}", s)).unwrap_or_default(),
+                    b.title().clone(),
+                ],
+                SearchResult::LiveRoom(r) => vec![
+                    if *r.is_live() { "live" } else { "offline" }.to_owned(),
+                    count(*r.online()),
+                    r.uname().clone(),
+                    r.title().clone(),
+                ],
+                SearchResult::Article(a) => {
+                    vec![count(*a.view()), count(*a.like()), a.title().clone()]
+                }
+            });
+            row
+        })
+        .collect::<Vec<_>>();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    // the last column is left ragged
+    let widths = (0..columns.saturating_sub(1))
+        .map(|c| {
+            rows.iter()
+                .filter_map(|r| r.get(c))
+                .map(|s| width(s))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let mut out = String::new();
+    for row in rows {
+        for (c, cell) in row.iter().enumerate() {
+            out.push_str(cell);
+            if let Some(w) = widths.get(c) {
+                out.push_str(&" ".repeat(w - width(cell) + 2));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// terminal columns, wide characters take two
+fn width(s: &str) -> usize {
+    s.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+fn clock(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// `12345` as `1.2万` like the site does
+fn count(n: u64) -> String {
+    match n {
+        0..=9999 => n.to_string(),
+        10000..=99_999_999 => format!("{:.1}万", n as f64 / 1e4),
+        _ => format!("{:.1}亿", n as f64 / 1e8),
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::server::*;
+
+macro_rules! fixtures {
+    ($($name:literal),* $(,)?) => {
+        &[$(($name, include_str!(concat!("../fixtures/", $name, ".json")))),*]
+    };
+}
+
+/// `{endpoint}/{bvid}` → api response, `{{host}}` in urls is replaced by the server
+const FIXTURES: &[(&str, &str)] = fixtures![
+    "nav",
+    "pagelist/BV1Rs4y1c7Mp",
+    "pagelist/BV1Vh4y1v7qn",
+    "pagelist/BV1qJ4m1Y71G",
+    "player_v2/BV1Vh4y1v7qn",
+    "playurl/BV1qJ4m1Y71G",
+    "playurl_dash/BV1qJ4m1Y71G",
+    "search/article-1",
+    "search/bili_user-1",
+    "search/live_room-1",
+    "search/media_bangumi-1",
+    "search/video-1",
+    "search/video-2",
+    "space_arc/1234567-1",
+    "space_arc/1234567-2",
+    "spi",
+    "subtitle/1233524563-ai-en",
+    "subtitle/1233524563-zh-CN",
+    "view/BV13m421J7fM",
+    "view/BV1nr421t7KX",
+    "view_detail/BV13m421J7fM",
+    "view_detail/BV1qJ4m1Y71G",
+];
+
+/// bilibili answers unknown videos with 200 and an error code
+const NOT_FOUND: &str = r#"{"code":-404,"message":"啥都木有","ttl":1}"#;
+const SIGN_REJECTED: &str = r#"{"code":-403,"message":"访问权限不足","ttl":1}"#;
+/// search or space listing without a `buvid3` cookie
+const BLOCKED: &str = r#"{"code":-412,"message":"请求被拦截","ttl":1}"#;
+
+/// fake api.bilibili.com serving `fixtures/`, doubles as the cdn under `/media/`
+///
+/// use `host()` as api host with `Protocol::HTTP`
+pub struct FakeBili {
+    server: MockServer,
+    fixtures: Arc<Mutex<HashMap<String, String>>>,
+    media: Arc<Mutex<HashMap<String, Arc<[u8]>>>>,
+}
+
+impl FakeBili {
+    pub async fn start() -> Self {
+        let fixtures = Arc::new(Mutex::new(
+            FIXTURES
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+        ));
+        let media = Arc::new(Mutex::new(HashMap::<String, Arc<[u8]>>::new()));
+        let server = {
+            let (fixtures, media) = (fixtures.clone(), media.clone());
+            MockServer::start(move |req| {
+                if let Some(name) = req.path.strip_prefix("/media/") {
+                    // like the image cdn, ignore `@{w}w_{h}h.{format}` and serve the original
+                    let name = name.split_once('@').map_or(name, |(name, _)| name);
+                    return match media.lock().unwrap().get(name) {
+                        Some(body) => range_response(body, req.header("range")),
+                        None => MockResponse::not_found(),
+                    };
+                }
+                let Some(name) = fixture_name(req) else {
+                    return MockResponse::not_found();
+                };
+                let signed = ["player_v2/", "search/", "space_arc/"]
+                    .iter()
+                    .any(|p| name.starts_with(p));
+                if signed
+                    && (req.query_param("w_rid").is_none() || req.query_param("wts").is_none())
+                {
+                    return MockResponse::json(SIGN_REJECTED);
+                }
+                let buvid = req.header("cookie").is_some_and(|c| c.contains("buvid3="));
+                if (name.starts_with("search/") || name.starts_with("space_arc/")) && !buvid {
+                    return MockResponse::json(BLOCKED);
+                }
+                let host = format!("http://{}", req.header("host").unwrap_or_default());
+                match fixtures.lock().unwrap().get(&name) {
+                    Some(body) => MockResponse::json(body.replace("{{host}}", &host)),
+                    None => MockResponse::json(NOT_FOUND),
+                }
+            })
+            .await
+        };
+        Self {
+            server,
+            fixtures,
+            media,
+        }
+    }
+
+    /// `127.0.0.1:{port}`
+    pub fn host(&self) -> &str {
+        self.server.host()
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.server.requests()
+    }
+
+    /// add or replace the response of `{endpoint}/{bvid}`, e.g. `view/BV1xx411c7mD`
+    pub fn fixture(&self, name: &str, body: impl Into<String>) {
+        self.fixtures
+            .lock()
+            .unwrap()
+            .insert(name.to_owned(), body.into());
+    }
+
+    /// serve `body` at `/media/{name}` with range support, returns its url
+    pub fn media(&self, name: &str, body: impl Into<Arc<[u8]>>) -> String {
+        self.media
+            .lock()
+            .unwrap()
+            .insert(name.to_owned(), body.into());
+        format!("http://{}/media/{}", self.host(), name)
+    }
+}
+
+/// fixture answering `req`, playurl picks `playurl_dash` when `fnval` asks for dash
+fn fixture_name(req: &MockRequest) -> Option<String> {
+    // bcc subtitles live on their own cdn, named by the fixture
+    if let Some(name) = req.path.strip_prefix("/bfs/subtitle/") {
+        return Some(format!("subtitle/{}", name.strip_suffix(".json")?));
+    }
+    let endpoint = match req.path.as_str() {
+        "/x/web-interface/nav" => return Some("nav".to_owned()),
+        "/x/frontend/finger/spi" => return Some("spi".to_owned()),
+        "/x/web-interface/wbi/search/type" => {
+            let page = req.query_param("page").unwrap_or("1");
+            return Some(format!(
+                "search/{}-{}",
+                req.query_param("search_type")?,
+                page
+            ));
+        }
+        "/x/space/wbi/arc/search" => {
+            let pn = req.query_param("pn").unwrap_or("1");
+            return Some(format!("space_arc/{}-{}", req.query_param("mid")?, pn));
+        }
+        "/x/player/pagelist" => "pagelist",
+        "/x/player/wbi/v2" => "player_v2",
+        "/x/web-interface/view" => "view",
+        "/x/web-interface/view/detail" => "view_detail",
+        "/x/player/playurl" => {
+            let fnval = req.query_param("fnval").and_then(|v| v.parse::<u32>().ok());
+            match fnval {
+                Some(fnval) if fnval & 16 != 0 => "playurl_dash",
+                _ => "playurl",
+            }
+        }
+        _ => return None,
+    };
+    let id = req
+        .query_param("bvid")
+        .or_else(|| req.query_param("aid"))
+        .or_else(|| req.query_param("avid"))?;
+    Some(format!("{}/{}", endpoint, id))
+}
@@ -0,0 +1,10 @@
+//! in-process fake bilibili api for offline tests
+//!
+//! `MockServer` answers every request with a handler, `FakeBili` is a
+//! `MockServer` serving recorded api fixtures and media files
+
+mod fake;
+mod server;
+
+pub use fake::*;
+pub use server::*;
@@ -0,0 +1,275 @@
+//! tiny in-process http server
+
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// decoded pairs of `query` and of a form `body`
+    query_params: Vec<(String, String)>,
+    form_params: Vec<(String, String)>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        param(&self.query_params, name)
+    }
+
+    /// field of a `application/x-www-form-urlencoded` body
+    pub fn form_param(&self, name: &str) -> Option<&str> {
+        param(&self.form_params, name)
+    }
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// percent-decoded pairs of an urlencoded string, `+` is a space
+fn decode_params(s: &str) -> Vec<(String, String)> {
+    let decode = |v: &str| {
+        percent_encoding::percent_decode_str(&v.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+    s.split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (decode(k), decode(v)))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn json(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+            body: body.into().into_bytes(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self {
+            status: 404,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+impl MockResponse {
+    pub fn bytes(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+}
+
+/// static file answering `Range: bytes={start}-{end}` and `bytes={start}-` like a cdn,
+/// requests without a range get the whole file
+pub fn serve_range(body: impl Into<Arc<[u8]>>) -> impl Fn(&MockRequest) -> MockResponse {
+    let body = body.into();
+    move |req| range_response(&body, req.header("range"))
+}
+
+pub(crate) fn range_response(body: &[u8], range: Option<&str>) -> MockResponse {
+    let Some(range) = range else {
+        return MockResponse::bytes(200, body).header("Accept-Ranges", "bytes");
+    };
+    let len = body.len();
+    let parsed = range
+        .strip_prefix("bytes=")
+        .and_then(|r| r.split_once('-'))
+        .and_then(|(s, e)| {
+            let start = s.trim().parse::<usize>().ok()?;
+            let end = match e.trim() {
+                "" => len.checked_sub(1)?,
+                e => e.parse::<usize>().ok()?.min(len.checked_sub(1)?),
+            };
+            (start <= end).then_some((start, end))
+        });
+    match parsed {
+        Some((start, end)) => MockResponse::bytes(206, &body[start..=end])
+            .header("Content-Range", &format!("bytes {}-{}/{}", start, end, len)),
+        None => {
+            MockResponse::bytes(416, vec![]).header("Content-Range", &format!("bytes */{}", len))
+        }
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+pub struct MockServer {
+    host: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock server");
+        let host = listener.local_addr().expect("mock server addr").to_string();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let task = {
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let handler = handler.clone();
+                    let requests = requests.clone();
+                    tokio::spawn(async move {
+                        let _ = serve(stream, handler, requests).await;
+                    });
+                }
+            })
+        };
+        Self {
+            host,
+            requests,
+            task,
+        }
+    }
+
+    /// `127.0.0.1:{port}`, used as api host with `Protocol::HTTP`
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(
+    stream: tokio::net::TcpStream,
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_owned();
+        let target = parts.next().unwrap_or_default();
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let (path, query) = (path.to_owned(), query.to_owned());
+
+        let mut headers = vec![];
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((k, v)) = line.split_once(':') {
+                headers.push((k.trim().to_owned(), v.trim().to_owned()));
+            }
+        }
+        let len = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, v)| v.parse::<usize>().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).await?;
+
+        let req = MockRequest {
+            query_params: decode_params(&query),
+            form_params: std::str::from_utf8(&body)
+                .map(decode_params)
+                .unwrap_or_default(),
+            method,
+            path,
+            query,
+            headers,
+            body,
+        };
+        requests.lock().unwrap().push(req.clone());
+        let resp = handler(&req);
+
+        let mut head = format!("HTTP/1.1 {} MOCK\r\n", resp.status);
+        for (k, v) in resp.headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", resp.body.len()));
+        let stream = stream.get_mut();
+        stream.write_all(head.as_bytes()).await?;
+        if req.method != "HEAD" {
+            stream.write_all(&resp.body).await?;
+        }
+        stream.flush().await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_response() {
+        let body = b"0123456789";
+        let resp = range_response(body, Some("bytes=2-4"));
+        assert_eq!((resp.status, resp.body.as_slice()), (206, &b"234"[..]));
+        assert!(resp
+            .headers
+            .contains(&("Content-Range".to_owned(), "bytes 2-4/10".to_owned())));
+        // open ended and past the end are clamped
+        assert_eq!(range_response(body, Some("bytes=7-")).body, b"789");
+        assert_eq!(range_response(body, Some("bytes=8-100")).body, b"89");
+        assert_eq!(range_response(body, Some("bytes=10-12")).status, 416);
+        assert_eq!(range_response(body, None).status, 200);
+    }
+
+    #[test]
+    fn test_decode_params() {
+        let params = decode_params("keyword=%E6%B5%8B%E8%AF%84&q=a+b%2Bc&empty=&bare");
+        assert_eq!(param(&params, "keyword"), Some("测评"));
+        assert_eq!(param(&params, "q"), Some("a b+c"));
+        assert_eq!(param(&params, "empty"), Some(""));
+        assert_eq!(param(&params, "bare"), None);
+    }
+}
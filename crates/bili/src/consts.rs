@@ -1,2 +1,21 @@
 pub const HOST: &str = "api.bilibili.com";
- 
\ No newline at end of file
+pub const PASSPORT_HOST: &str = "passport.bilibili.com";
+pub const WWW_HOST: &str = "www.bilibili.com";
+/// domains of api and web hosts, for proxy routing
+pub const API_DOMAINS: &[&str] = &["bilibili.com"];
+/// domains serving video streams and images
+pub const CDN_DOMAINS: &[&str] = &["bilivideo.com", "bilivideo.cn", "akamaized.net", "hdslb.com"];
+/// query, form and json fields carrying login secrets, scrubbed from cassettes
+pub const SECRET_PARAMS: &[&str] = &[
+    "SESSDATA",
+    "bili_jct",
+    "DedeUserID__ckMd5",
+    "csrf",
+    "refresh_csrf",
+    "refresh_token",
+    "access_key",
+];
+/// params of wbi signed apis changing on every request
+pub const VOLATILE_PARAMS: &[&str] = &["wts", "w_rid"];
+pub const REFERER: &str = "https://www.bilibili.com";
+pub const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36 Edg/122.0.0.0";
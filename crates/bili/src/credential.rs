@@ -0,0 +1,298 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+use super::error::*;
+
+pub const COOKIE_DOMAIN: &str = ".bilibili.com";
+
+// https://github.com/SocialSisterYi/bilibili-API-collect/blob/master/docs/login/cookie_refresh.md
+const CORRESPOND_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDLgd2OAkcGVtoE3ThUREbio0Eg
+Uc/prcajMKXvkCKFCWhJYJcLkcM2DKKcSeFpD/j6Boy538YXnR6VhcuUJOhH2x71
+nzPjfdTcqMz7djHum0qSZA0AyCBDABUqCrfNgCiJ00Ra7GmRj+YCK1NJEuewlb40
+JNrRuoEUXpabUzGB8QIDAQAB
+-----END PUBLIC KEY-----";
+
+/// login cookies of a bilibili account
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize, Getters)]
+pub struct Credential {
+    #[serde(rename = "SESSDATA")]
+    sessdata: String,
+    #[serde(default)]
+    bili_jct: String,
+    #[serde(rename = "DedeUserID", default)]
+    dede_user_id: String,
+    /// `ac_time_value`, given by qrcode login and used to refresh cookies
+    #[serde(default)]
+    refresh_token: String,
+}
+
+/// only the account id, the cookies and refresh_token are secrets
+impl std::fmt::Debug for Credential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credential")
+            .field("dede_user_id", &self.dede_user_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Credential {
+    pub fn new(sessdata: String, bili_jct: String, dede_user_id: String) -> Self {
+        Self {
+            sessdata,
+            bili_jct,
+            dede_user_id,
+            refresh_token: String::new(),
+        }
+    }
+
+    pub fn with_refresh_token(mut self, refresh_token: String) -> Self {
+        self.refresh_token = refresh_token;
+        self
+    }
+
+    /// parse a `Cookie` header like `SESSDATA=xxx; bili_jct=xxx; DedeUserID=xxx`
+    pub fn from_cookie_str(s: &str) -> Result<Self> {
+        Self::from_pairs(s.split(';').filter_map(|kv| {
+            let (k, v) = kv.split_once('=')?;
+            Some((k.trim(), v.trim()))
+        }))
+    }
+
+    /// parse a Netscape `cookies.txt`, only `bilibili.com` cookies are used
+    pub fn from_netscape(s: &str) -> Result<Self> {
+        Self::from_pairs(s.lines().filter_map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let fields = line.split('\t').collect::<Vec<_>>();
+            match fields[..] {
+                [domain, _, _, _, _, name, value] if domain.ends_with("bilibili.com") => {
+                    Some((name, value))
+                }
+                _ => None,
+            }
+        }))
+    }
+
+    /// parse either `{"SESSDATA": "..", ..}` or a browser export `[{"name": "..", "value": ".."}]`
+    pub fn from_json(s: &str) -> Result<Self> {
+        let value = serde_json::from_str::<serde_json::Value>(s)
+            .map_err(|e| Error::InvalidCredential(e.to_string()))?;
+        match value {
+            serde_json::Value::Array(cookies) => {
+                Self::from_pairs(cookies.iter().filter_map(|c| {
+                    Some((c.get("name")?.as_str()?, c.get("value")?.as_str()?))
+                }))
+            }
+            value => serde_json::from_value(value)
+                .map_err(|e| Error::InvalidCredential(e.to_string())),
+        }
+    }
+
+    /// load from a cookies.txt or json file, format is detected by content
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        match content.trim_start().chars().next() {
+            Some('{') | Some('[') => Self::from_json(&content),
+            _ => Self::from_netscape(&content),
+        }
+    }
+
+    pub(crate) fn from_pairs<'a>(pairs: impl Iterator<Item = (&'a str, &'a str)>) -> Result<Self> {
+        let mut c = Self::default();
+        for (k, v) in pairs {
+            match k {
+                "SESSDATA" => c.sessdata = v.to_owned(),
+                "bili_jct" => c.bili_jct = v.to_owned(),
+                "DedeUserID" => c.dede_user_id = v.to_owned(),
+                _ => {}
+            }
+        }
+        if c.sessdata.is_empty() {
+            return Err(Error::InvalidCredential("SESSDATA not found".to_owned()));
+        }
+        Ok(c)
+    }
+
+    /// `SESSDATA` and `bili_jct` are both needed by apis with csrf
+    pub(crate) fn require_login(&self) -> Result<()> {
+        if self.sessdata.is_empty() || self.bili_jct.is_empty() {
+            Err(Error::InvalidCredential("SESSDATA or bili_jct missing".to_owned()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// `name=value` pairs to put into a cookie jar
+    pub(crate) fn cookies(&self) -> Vec<(&str, &str)> {
+        [
+            ("SESSDATA", self.sessdata.as_str()),
+            ("bili_jct", self.bili_jct.as_str()),
+            ("DedeUserID", self.dede_user_id.as_str()),
+        ]
+        .into_iter()
+        .filter(|(_, v)| !v.is_empty())
+        .collect()
+    }
+}
+
+/// `correspondPath` of the cookie refresh flow, hex of RSA-OAEP(`refresh_{timestamp}`)
+pub(crate) fn correspond_path(timestamp: u64) -> Result<String> {
+    use rsa::pkcs8::DecodePublicKey;
+
+    let key = rsa::RsaPublicKey::from_public_key_pem(CORRESPOND_PUBLIC_KEY)
+        .map_err(|e| Error::Unknown(e.to_string()))?;
+    encrypt_correspond(&key, timestamp)
+}
+
+fn encrypt_correspond(key: &rsa::RsaPublicKey, timestamp: u64) -> Result<String> {
+    let padding = rsa::Oaep::new::<rsa::sha2::Sha256>();
+    let encrypted = key
+        .encrypt(
+            &mut rand::thread_rng(),
+            padding,
+            format!("refresh_{}", timestamp).as_bytes(),
+        )
+        .map_err(|e| Error::Unknown(e.to_string()))?;
+    Ok(encrypted.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// where refreshed credentials are written back
+pub trait CredentialStore {
+    fn load(&self) -> Result<Option<Credential>>;
+
+    fn save(&self, credential: &Credential) -> Result<()>;
+}
+
+/// json file store, the format `Credential::from_file` reads
+#[derive(Debug, Clone)]
+pub struct FileCredentialStore {
+    path: std::path::PathBuf,
+}
+
+impl FileCredentialStore {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+impl CredentialStore for FileCredentialStore {
+    fn load(&self) -> Result<Option<Credential>> {
+        if self.path.is_file() {
+            Credential::from_file(&self.path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn save(&self, credential: &Credential) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_vec_pretty(credential)
+            .map_err(|e| Error::InvalidCredential(e.to_string()))?;
+        // write then rename, a crash never leaves a half written file
+        let tmp = self.path.with_extension("tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.create(true).truncate(true).write(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut f = options.open(&tmp)?;
+        std::io::Write::write_all(&mut f, &content)?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expect() -> Credential {
+        Credential::new("abc%2C123".to_owned(), "jct".to_owned(), "42".to_owned())
+    }
+
+    #[test]
+    fn test_from_cookie_str() -> anyhow::Result<()> {
+        let c = Credential::from_cookie_str("buvid3=x; SESSDATA=abc%2C123;bili_jct=jct; DedeUserID=42")?;
+        assert_eq!(c, expect());
+        assert!(Credential::from_cookie_str("bili_jct=jct").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_netscape() -> anyhow::Result<()> {
+        let c = Credential::from_netscape(
+            "# Netscape HTTP Cookie File\n\
+             .example.com\tTRUE\t/\tFALSE\t0\tSESSDATA\tother\n\
+             #HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t1735660800\tSESSDATA\tabc%2C123\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t1735660800\tbili_jct\tjct\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t1735660800\tDedeUserID\t42\n",
+        )?;
+        assert_eq!(c, expect());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_json() -> anyhow::Result<()> {
+        let c = Credential::from_json(
+            r#"{"SESSDATA": "abc%2C123", "bili_jct": "jct", "DedeUserID": "42"}"#,
+        )?;
+        assert_eq!(c, expect());
+        let c = Credential::from_json(
+            r#"[{"domain": ".bilibili.com", "name": "SESSDATA", "value": "abc%2C123"},
+                {"domain": ".bilibili.com", "name": "bili_jct", "value": "jct"},
+                {"domain": ".bilibili.com", "name": "DedeUserID", "value": "42"}]"#,
+        )?;
+        assert_eq!(c, expect());
+        assert!(Credential::from_json("[]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_redacted() {
+        let debug = format!("{:?}", expect().with_refresh_token("token".to_owned()));
+        assert_eq!(debug, r#"Credential { dede_user_id: "42", .. }"#);
+    }
+
+    #[test]
+    fn test_correspond_path() -> anyhow::Result<()> {
+        use rsa::traits::PublicKeyParts;
+
+        let path = correspond_path(1684466082000)?;
+        // 1024 bits key
+        assert_eq!(path.len(), 256);
+        assert!(path.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let private = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let public = rsa::RsaPublicKey::new(private.n().clone(), private.e().clone())?;
+        let path = encrypt_correspond(&public, 42)?;
+        let bytes = (0..path.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&path[i..i + 2], 16))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let plain = private.decrypt(rsa::Oaep::new::<rsa::sha2::Sha256>(), &bytes)?;
+        assert_eq!(plain, b"refresh_42");
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_store() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("bili-store-{}", std::process::id()));
+        let store = FileCredentialStore::new(dir.join("credential.json"));
+        assert_eq!(store.load()?, None);
+        let c = expect().with_refresh_token("token".to_owned());
+        store.save(&c)?;
+        assert_eq!(store.load()?, Some(c));
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use std::fmt::Write;
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+
+use super::{Danmaku, DanmakuMode};
+use crate::subtitle::{ass_clock, ass_escape};
+
+/// layout of `render_ass`, sizes are pixels of the `width`x`height` canvas
+#[derive(Debug, Clone, Builder, Getters)]
+#[builder(default)]
+pub struct DanmakuAssOptions {
+    width: u32,
+    height: u32,
+    #[builder(setter(into))]
+    font_name: String,
+    /// size of a normal (25) danmaku, others scale along
+    font_size: u32,
+    /// a scrolling danmaku crosses the screen in this time
+    scroll_ms: u64,
+    /// a top or bottom danmaku stays this long
+    fixed_ms: u64,
+    /// part of the height danmaku may cover from the top, `1.0` for all of it
+    area: f64,
+    /// transparency of the text, 0 opaque and 255 invisible
+    alpha: u8,
+}
+
+impl Default for DanmakuAssOptions {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            font_name: "sans-serif".to_owned(),
+            font_size: 50,
+            scroll_ms: 8000,
+            fixed_ms: 4000,
+            area: 1.0,
+            alpha: 0x40,
+        }
+    }
+}
+
+/// a scrolling danmaku in a row, enough to tell if a later one would catch up
+#[derive(Debug, Clone, Copy)]
+struct Scrolling {
+    start: f64,
+    width: f64,
+    /// pixels per millisecond
+    speed: f64,
+}
+
+/// rows of the screen, each remembers what was put there last
+struct Layout<'a> {
+    options: &'a DanmakuAssOptions,
+    scroll: Vec<Option<Scrolling>>,
+    /// end time of the last top/bottom danmaku of each row
+    top: Vec<u64>,
+    bottom: Vec<u64>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(options: &'a DanmakuAssOptions) -> Self {
+        let area = (options.height as f64 * options.area.clamp(0.0, 1.0)) as u32;
+        let rows = (area / options.font_size.max(1)).max(1) as usize;
+        Self {
+            options,
+            scroll: vec![None; rows],
+            top: vec![0; rows],
+            bottom: vec![0; rows],
+        }
+    }
+
+    /// first row where `span` rows from it are free for a scrolling danmaku
+    fn place_scroll(&mut self, start: u64, width: f64, span: usize) -> Option<usize> {
+        let d = self.options.scroll_ms as f64;
+        let screen = self.options.width as f64;
+        let new = Scrolling {
+            start: start as f64,
+            width,
+            speed: (screen + width) / d,
+        };
+        let fits = |prev: &Option<Scrolling>| match prev {
+            None => true,
+            Some(prev) => {
+                // the tail of prev is on the screen when new appears
+                let entered = new.start >= prev.start + prev.width / prev.speed;
+                // new reaches the left edge before prev is gone
+                let caught =
+                    new.speed > prev.speed && new.start + screen / new.speed < prev.start + d;
+                entered && !caught
+            }
+        };
+        let row = (0..=self.scroll.len().checked_sub(span)?)
+            .find(|&row| self.scroll[row..row + span].iter().all(fits))?;
+        self.scroll[row..row + span].fill(Some(new));
+        Some(row)
+    }
+
+    fn place_fixed(&mut self, start: u64, span: usize, bottom: bool) -> Option<usize> {
+        let end = start + self.options.fixed_ms;
+        let rows = if bottom {
+            &mut self.bottom
+        } else {
+            &mut self.top
+        };
+        let row = (0..=rows.len().checked_sub(span)?)
+            .find(|&row| rows[row..row + span].iter().all(|&e| e <= start))?;
+        rows[row..row + span].fill(end);
+        Some(row)
+    }
+}
+
+/// lay `danmakus` out into an ASS subtitle
+///
+/// scrolling danmaku never overlap, those finding no free row are dropped,
+/// so are reverse and scripted ones
+pub fn render_ass(danmakus: &[Danmaku], options: &DanmakuAssOptions) -> String {
+    let mut out = ass_header(options);
+    let mut layout = Layout::new(options);
+    let mut danmakus = danmakus.iter().collect::<Vec<_>>();
+    danmakus.sort_by_key(|d| d.time_ms);
+
+    let row_h = options.font_size.max(1);
+    for d in danmakus {
+        let text = d
+            .content
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" ");
+        if text.is_empty() {
+            continue;
+        }
+        // the player offers 12 to 64, anything else is bogus data
+        let font_size = options
+            .font_size
+            .saturating_mul(d.font_size.clamp(12, 64))
+            .div_ceil(25)
+            .max(1);
+        let span = font_size.div_ceil(row_h) as usize;
+        let width = text_width(&text, font_size);
+        let start = d.time_ms;
+        let (end, position) = match d.mode {
+            DanmakuMode::Scroll => {
+                let Some(row) = layout.place_scroll(start, width, span) else {
+                    continue;
+                };
+                let y = row as u32 * row_h;
+                let position = format!(
+                    "\\move({},{},{},{})",
+                    options.width,
+                    y,
+                    -(width.ceil() as i64),
+                    y
+                );
+                (start + options.scroll_ms, position)
+            }
+            DanmakuMode::Top | DanmakuMode::Bottom => {
+                let bottom = d.mode == DanmakuMode::Bottom;
+                let Some(row) = layout.place_fixed(start, span, bottom) else {
+                    continue;
+                };
+                let position = if bottom {
+                    format!(
+                        "\\an2\\pos({},{})",
+                        options.width / 2,
+                        options.height - row as u32 * row_h
+                    )
+                } else {
+                    format!("\\an8\\pos({},{})", options.width / 2, row as u32 * row_h)
+                };
+                (start + options.fixed_ms, position)
+            }
+            _ => continue,
+        };
+        let _ = writeln!(
+            out,
+            "Dialogue: 2,{},{},Danmaku,,0,0,0,,{{{}{}}}{}",
+            ass_clock(start),
+            ass_clock(end),
+            position,
+            overrides(d, font_size, options),
+            ass_escape(&text)
+        );
+    }
+    out
+}
+
+fn ass_header(options: &DanmakuAssOptions) -> String {
+    format!(
+        "[Script Info]
+ScriptType: v4.00+
+PlayResX: {width}
+PlayResY: {height}
+WrapStyle: 2
+ScaledBorderAndShadow: yes
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Danmaku,{font},{size},&H{alpha:02X}FFFFFF,&H{alpha:02X}FFFFFF,&H{alpha:02X}000000,&H{alpha:02X}000000,1,0,0,0,100,100,0,0,1,1,0,7,0,0,0,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+",
+        width = options.width,
+        height = options.height,
+        font = options.font_name,
+        size = options.font_size,
+        alpha = options.alpha,
+    )
+}
+
+/// color and size differing from the style
+fn overrides(d: &Danmaku, font_size: u32, options: &DanmakuAssOptions) -> String {
+    let mut tags = String::new();
+    if font_size != options.font_size {
+        let _ = write!(tags, "\\fs{}", font_size);
+    }
+    let color = d.color & 0xffffff;
+    if color != 0xffffff {
+        let (r, g, b) = (color >> 16, (color >> 8) & 0xff, color & 0xff);
+        let _ = write!(tags, "\\c&H{:02X}{:02X}{:02X}&", b, g, r);
+        // dark text is unreadable with a black border
+        if r * 299 + g * 587 + b * 114 < 0x30 * 1000 {
+            tags.push_str("\\3c&HFFFFFF&");
+        }
+    }
+    tags
+}
+
+/// estimated, full-width characters are as wide as the font size and the others half
+fn text_width(text: &str, font_size: u32) -> f64 {
+    let units = text
+        .chars()
+        .map(|c| if c.is_ascii() { 0.5 } else { 1.0 })
+        .sum::<f64>();
+    units * font_size as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(ass: &str) -> Vec<&str> {
+        ass.lines()
+            .filter_map(|l| l.strip_prefix("Dialogue: 2,"))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_scroll() -> anyhow::Result<()> {
+        let options = DanmakuAssOptionsBuilder::default()
+            .width(1000)
+            .height(100)
+            .font_size(50)
+            .scroll_ms(10000)
+            .build()?;
+        let danmakus = vec![
+            // 100px wide, its tail enters the screen after 1s
+            Danmaku::new(0, DanmakuMode::Scroll, "汉字"),
+            Danmaku::new(500, DanmakuMode::Scroll, "ab"),
+            // both rows taken, dropped
+            Danmaku::new(600, DanmakuMode::Scroll, "ab"),
+            // row 0 again, short and slower than the first, never catches up
+            Danmaku::new(1000, DanmakuMode::Scroll, "ab"),
+            // long and fast, would catch up with every one on the screen
+            Danmaku::new(1100, DanmakuMode::Scroll, "a".repeat(80)),
+        ];
+        let ass = render_ass(&danmakus, &options);
+        assert!(ass.contains("PlayResX: 1000\nPlayResY: 100\n"));
+        assert_eq!(
+            events(&ass),
+            vec![
+                "0:00:00.00,0:00:10.00,Danmaku,,0,0,0,,{\\move(1000,0,-100,0)}汉字",
+                "0:00:00.50,0:00:10.50,Danmaku,,0,0,0,,{\\move(1000,50,-50,50)}ab",
+                "0:00:01.00,0:00:11.00,Danmaku,,0,0,0,,{\\move(1000,0,-50,0)}ab",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_fixed() -> anyhow::Result<()> {
+        let options = DanmakuAssOptionsBuilder::default()
+            .width(1000)
+            .height(200)
+            .font_size(50)
+            .fixed_ms(3000)
+            .build()?;
+        let danmakus = vec![
+            Danmaku::new(0, DanmakuMode::Top, "顶").with_color(0xff0000),
+            Danmaku::new(100, DanmakuMode::Top, "大").with_font_size(36),
+            Danmaku::new(200, DanmakuMode::Bottom, "底{}").with_color(0x000000),
+            Danmaku::new(3000, DanmakuMode::Top, "再"),
+            // clamped to 64, 128px does not fit
+            Danmaku::new(250, DanmakuMode::Top, "巨").with_font_size(u32::MAX),
+            Danmaku::new(300, DanmakuMode::Reverse, "逆"),
+            Danmaku::new(400, DanmakuMode::Advanced, "[0,0,\"1-1\",4.5]"),
+        ];
+        assert_eq!(
+            events(&render_ass(&danmakus, &options)),
+            vec![
+                "0:00:00.00,0:00:03.00,Danmaku,,0,0,0,,{\\an8\\pos(500,0)\\c&H0000FF&}顶",
+                // 72px, takes two rows
+                "0:00:00.10,0:00:03.10,Danmaku,,0,0,0,,{\\an8\\pos(500,50)\\fs72}大",
+                "0:00:00.20,0:00:03.20,Danmaku,,0,0,0,,{\\an2\\pos(500,200)\\c&H000000&\\3c&HFFFFFF&}底\\{\\}",
+                "0:00:03.00,0:00:06.00,Danmaku,,0,0,0,,{\\an8\\pos(500,0)}再",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+mod ass;
+mod proto;
+
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+use super::error::*;
+
+pub use ass::*;
+
+/// `seg.so` splits danmaku of a page into segments of 6 minutes
+pub const DANMAKU_SEGMENT_SECS: u64 = 360;
+
+/// a bullet comment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+pub struct Danmaku {
+    id: u64,
+    /// shown at this millisecond of the video
+    time_ms: u64,
+    mode: DanmakuMode,
+    /// 25 is the normal size, 18 small and 36 large
+    font_size: u32,
+    /// `0xRRGGBB`
+    color: u32,
+    /// crc32 of the sender's mid in hex
+    sender_hash: String,
+    content: String,
+    /// unix seconds it was sent
+    ctime: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DanmakuMode {
+    /// right to left
+    Scroll,
+    Bottom,
+    Top,
+    /// left to right
+    Reverse,
+    /// positioned and animated by a script, not rendered
+    Advanced,
+    Other(u8),
+}
+
+impl DanmakuMode {
+    pub fn from_code(code: i64) -> Self {
+        match code {
+            1..=3 => DanmakuMode::Scroll,
+            4 => DanmakuMode::Bottom,
+            5 => DanmakuMode::Top,
+            6 => DanmakuMode::Reverse,
+            7 => DanmakuMode::Advanced,
+            code => DanmakuMode::Other(code.clamp(0, u8::MAX as i64) as u8),
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            DanmakuMode::Scroll => 1,
+            DanmakuMode::Bottom => 4,
+            DanmakuMode::Top => 5,
+            DanmakuMode::Reverse => 6,
+            DanmakuMode::Advanced => 7,
+            DanmakuMode::Other(code) => *code,
+        }
+    }
+}
+
+impl Danmaku {
+    pub fn new(time_ms: u64, mode: DanmakuMode, content: impl Into<String>) -> Self {
+        Self {
+            id: 0,
+            time_ms,
+            mode,
+            font_size: 25,
+            color: 0xffffff,
+            sender_hash: String::new(),
+            content: content.into(),
+            ctime: 0,
+        }
+    }
+
+    pub fn with_font_size(mut self, font_size: u32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_color(mut self, color: u32) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// `<d p="time,mode,size,color,ctime,pool,sender_hash,id,...">content</d>` of `list.so`
+    pub fn from_xml(xml: &str) -> Result<Vec<Danmaku>> {
+        use quick_xml::events::Event;
+
+        let invalid = |e: &dyn std::fmt::Display| Error::InvalidDanmaku(e.to_string());
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let mut danmakus = vec![];
+        // attributes of the open `<d>`, content follows as text
+        let mut open: Option<String> = None;
+        let mut content = String::new();
+        loop {
+            match reader.read_event().map_err(|e| invalid(&e))? {
+                Event::Start(e) if e.name().as_ref() == b"d" => {
+                    let p = e
+                        .try_get_attribute("p")
+                        .map_err(|e| invalid(&e))?
+                        .ok_or(Error::InvalidDanmaku("<d> without p".to_owned()))?;
+                    open = Some(p.unescape_value().map_err(|e| invalid(&e))?.into_owned());
+                    content.clear();
+                }
+                Event::Text(e) if open.is_some() => {
+                    content.push_str(&e.unescape().map_err(|e| invalid(&e))?);
+                }
+                Event::End(e) if e.name().as_ref() == b"d" => {
+                    if let Some(p) = open.take() {
+                        danmakus.push(Self::from_xml_attr(&p, std::mem::take(&mut content))?);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(danmakus)
+    }
+
+    fn from_xml_attr(p: &str, content: String) -> Result<Self> {
+        let invalid = || Error::InvalidDanmaku(format!("bad p attribute: {}", p));
+        let fields = p.split(',').collect::<Vec<_>>();
+        if fields.len() < 8 {
+            return Err(invalid());
+        }
+        let num = |i: usize| fields[i].parse::<i64>().map_err(|_| invalid());
+        let time = fields[0].parse::<f64>().map_err(|_| invalid())?;
+        Ok(Self {
+            id: num(7)? as u64,
+            time_ms: (time.max(0.0) * 1000.0).round() as u64,
+            mode: DanmakuMode::from_code(num(1)?),
+            font_size: num(2)? as u32,
+            color: num(3)? as u32,
+            sender_hash: fields[6].to_owned(),
+            content,
+            ctime: num(4)?,
+        })
+    }
+
+    /// merge snapshots of the same page, the same danmaku (by id) kept once, in time order
+    pub fn merge(danmakus: impl IntoIterator<Item = Danmaku>) -> Vec<Danmaku> {
+        let mut seen = std::collections::HashSet::new();
+        let mut merged = danmakus
+            .into_iter()
+            .filter(|d| seen.insert(d.id))
+            .collect::<Vec<_>>();
+        merged.sort_by_key(|d| (d.time_ms, d.id));
+        merged
+    }
+
+    /// a `seg.so` response, `DmSegMobileReply` protobuf
+    pub fn from_segment(data: &[u8]) -> Result<Vec<Danmaku>> {
+        use prost::Message;
+
+        let reply = proto::DmSegMobileReply::decode(data)
+            .map_err(|e| Error::InvalidDanmaku(e.to_string()))?;
+        Ok(reply.elems.into_iter().map(Self::from).collect())
+    }
+}
+
+impl From<proto::DanmakuElem> for Danmaku {
+    fn from(e: proto::DanmakuElem) -> Self {
+        Self {
+            id: e.id as u64,
+            time_ms: e.progress.max(0) as u64,
+            mode: DanmakuMode::from_code(e.mode as i64),
+            font_size: e.fontsize as u32,
+            color: e.color,
+            sender_hash: e.mid_hash,
+            content: e.content,
+            ctime: e.ctime,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) use proto::encode_segment;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_xml() -> Result<()> {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?><i><chatserver>chat.bilibili.com</chatserver><chatid>1233524563</chatid><maxlimit>1500</maxlimit><state>0</state><real_name>0</real_name><source>k-v</source><d p="12.345,1,25,16777215,1713355200,0,8b2a4f1c,1415447423432704,11">第一 &amp; &lt;二&gt;</d><d p="3.5,5,36,16711680,1713355201,0,c0ffee00,1415447423432705,10">顶部</d><d p="0.1,4,18,255,1713355202,0,deadbeef,1415447423432706,10"></d></i>"#;
+        let danmakus = Danmaku::from_xml(xml)?;
+        assert_eq!(danmakus.len(), 3);
+        assert_eq!(
+            danmakus[0],
+            Danmaku {
+                id: 1415447423432704,
+                time_ms: 12345,
+                mode: DanmakuMode::Scroll,
+                font_size: 25,
+                color: 0xffffff,
+                sender_hash: "8b2a4f1c".to_owned(),
+                content: "第一 & <二>".to_owned(),
+                ctime: 1713355200,
+            }
+        );
+        assert_eq!(danmakus[1].mode, DanmakuMode::Top);
+        assert_eq!(danmakus[1].color, 0xff0000);
+        assert_eq!(danmakus[2].mode, DanmakuMode::Bottom);
+        assert_eq!(danmakus[2].content, "");
+
+        assert!(Danmaku::from_xml(r#"<i><d p="1,1">x</d></i>"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_segment() -> Result<()> {
+        let danmakus = vec![
+            Danmaku::new(1500, DanmakuMode::Scroll, "滚动").with_color(0x00ff00),
+            Danmaku::new(2000, DanmakuMode::Top, "顶部").with_font_size(18),
+        ];
+        let decoded = Danmaku::from_segment(&encode_segment(&danmakus))?;
+        assert_eq!(decoded, danmakus);
+        assert!(Danmaku::from_segment(b"\x0a\xff").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = Danmaku::new(2000, DanmakuMode::Scroll, "a").with_id(1);
+        let b = Danmaku::new(1000, DanmakuMode::Scroll, "b").with_id(2);
+        let c = Danmaku::new(1000, DanmakuMode::Top, "c").with_id(3);
+        let merged = Danmaku::merge([a.clone(), b.clone(), c.clone(), a.clone(), b.clone()]);
+        assert_eq!(merged, vec![b, c, a]);
+    }
+}
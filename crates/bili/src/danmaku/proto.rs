@@ -0,0 +1,56 @@
+//! messages of `bilibili.community.service.dm.v1` used by `seg.so`, fields we don't read are left out
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DmSegMobileReply {
+    #[prost(message, repeated, tag = "1")]
+    pub elems: Vec<DanmakuElem>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DanmakuElem {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    /// milliseconds
+    #[prost(int32, tag = "2")]
+    pub progress: i32,
+    #[prost(int32, tag = "3")]
+    pub mode: i32,
+    #[prost(int32, tag = "4")]
+    pub fontsize: i32,
+    #[prost(uint32, tag = "5")]
+    pub color: u32,
+    #[prost(string, tag = "6")]
+    pub mid_hash: String,
+    #[prost(string, tag = "7")]
+    pub content: String,
+    #[prost(int64, tag = "8")]
+    pub ctime: i64,
+    #[prost(int32, tag = "9")]
+    pub weight: i32,
+    #[prost(int32, tag = "11")]
+    pub pool: i32,
+}
+
+/// a `seg.so` body, for serving fake segments in tests
+#[cfg(test)]
+pub(crate) fn encode_segment(danmakus: &[super::Danmaku]) -> Vec<u8> {
+    use prost::Message;
+
+    DmSegMobileReply {
+        elems: danmakus
+            .iter()
+            .map(|d| DanmakuElem {
+                id: *d.id() as i64,
+                progress: *d.time_ms() as i32,
+                mode: d.mode().code() as i32,
+                fontsize: *d.font_size() as i32,
+                color: *d.color(),
+                mid_hash: d.sender_hash().clone(),
+                content: d.content().clone(),
+                ctime: *d.ctime(),
+                ..Default::default()
+            })
+            .collect(),
+    }
+    .encode_to_vec()
+}
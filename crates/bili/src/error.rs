@@ -12,10 +12,46 @@ pub enum Error {
     UnexpectedResp,
     #[error("api err,code: {0}, msg: {1}")]
     APIErr(i32, String),
+    #[error("unexpected http status: {0}")]
+    HttpStatus(u16),
+    #[error("range ignored by server, got 200 for bytes={0}-{1}")]
+    RangeIgnored(u64, u64),
+    #[error("content range mismatch, expect bytes {0}-{1}/{2} got {3:?}")]
+    ContentRangeMismatch(u64, u64, u64, Option<String>),
+    #[error("size mismatch, expect {0} got {1}")]
+    SizeMismatch(u64, u64),
+    #[error("invalid credential: {0}")]
+    InvalidCredential(String),
+    #[error("invalid danmaku: {0}")]
+    InvalidDanmaku(String),
     #[error("reqwest err: {0}")]
     ReqwestErr(#[from] reqwest::Error),
     #[error("unknown err: {0}")]
     Unknown(String),
 }
 
+impl Error {
+    /// transient failures worth another attempt: timeouts, resets, 5xx and short bodies
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::ReqwestErr(e) => network_tools::is_retryable_error(e),
+            Error::IOError(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::UnexpectedEof
+                    | std::io::ErrorKind::Interrupted
+            ),
+            Error::HttpStatus(status) => reqwest::StatusCode::from_u16(*status)
+                .is_ok_and(network_tools::is_retryable_status),
+            // a mirror may behave better
+            Error::RangeIgnored(_, _) | Error::ContentRangeMismatch(_, _, _, _) => true,
+            Error::SizeMismatch(_, _) => true,
+            _ => false,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,389 @@
+use super::*;
+
+impl<'a> prelude::AcconutService for &Service<'a> {
+    // GET /x/web-interface/nav
+    async fn get_nav_info(self) -> Result<NavInfo> {
+        let url = format!(
+            "{}{}/x/web-interface/nav",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        // not login returns -101 with `isLogin: false`
+        self.client
+            .get(url)
+            .send()
+            .await?
+            .json::<PackInfo<NavInfo>>()
+            .await?
+            .into_data()
+            .ok_or(Error::UnexpectedResp)
+    }
+
+    // GET /x/passport-login/web/qrcode/generate
+    async fn generate_qrcode(self) -> Result<QrCode> {
+        let url = format!(
+            "{}{}/x/passport-login/web/qrcode/generate",
+            self.protocol.get_prefix(),
+            self.passport_host
+        );
+        self.client
+            .get(url)
+            .send()
+            .await?
+            .json::<PackInfo<QrCode>>()
+            .await?
+            .as_result()
+    }
+
+    // GET /x/passport-login/web/qrcode/poll
+    async fn poll_qrcode(self, qrcode_key: &str) -> Result<QrLoginState> {
+        use serde::Deserialize;
+        #[derive(Debug, Deserialize)]
+        struct Poll {
+            url: String,
+            refresh_token: String,
+            code: i32,
+            message: String,
+        }
+
+        let url = format!(
+            "{}{}/x/passport-login/web/qrcode/poll",
+            self.protocol.get_prefix(),
+            self.passport_host
+        );
+        let resp = self
+            .client
+            .get(url)
+            .query(&[("qrcode_key", qrcode_key)])
+            .send()
+            .await?;
+        let cookies = resp
+            .cookies()
+            .map(|c| (c.name().to_owned(), c.value().to_owned()))
+            .collect::<Vec<_>>();
+        let poll = resp.json::<PackInfo<Poll>>().await?.as_result()?;
+        match poll.code {
+            0 => {
+                // cookies are also carried by the cross domain url query
+                let credential = if cookies.is_empty() {
+                    Credential::from_pairs(
+                        poll.url
+                            .split_once('?')
+                            .map_or("", |(_, q)| q)
+                            .split('&')
+                            .filter_map(|kv| kv.split_once('=')),
+                    )
+                } else {
+                    Credential::from_pairs(cookies.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+                }?;
+                Ok(QrLoginState::Confirmed(
+                    credential.with_refresh_token(poll.refresh_token),
+                ))
+            }
+            86101 => Ok(QrLoginState::Waiting),
+            86090 => Ok(QrLoginState::Scanned),
+            86038 => Ok(QrLoginState::Expired),
+            code => Err(Error::APIErr(code, poll.message)),
+        }
+    }
+
+    // GET /x/passport-login/web/cookie/info
+    async fn get_cookie_info(self) -> Result<CookieInfo> {
+        let credential = self.credential().unwrap_or_default();
+        credential.require_login()?;
+        let url = format!(
+            "{}{}/x/passport-login/web/cookie/info",
+            self.protocol.get_prefix(),
+            self.passport_host
+        );
+        self.client
+            .get(url)
+            .query(&[("csrf", credential.bili_jct())])
+            .send()
+            .await?
+            .json::<PackInfo<CookieInfo>>()
+            .await?
+            .as_result()
+    }
+
+    // GET /correspond/1/{path}
+    // POST /x/passport-login/web/cookie/refresh
+    // POST /x/passport-login/web/confirm/refresh
+    async fn refresh_credential<S>(self, store: &S) -> Result<Credential>
+    where
+        S: CredentialStore + Sync,
+    {
+        use serde::Deserialize;
+        #[derive(Debug, Deserialize)]
+        struct Refresh {
+            refresh_token: String,
+        }
+
+        let old = self.credential().unwrap_or_default();
+        old.require_login()?;
+        if old.refresh_token().is_empty() {
+            return Err(Error::InvalidCredential("refresh_token missing".to_owned()));
+        }
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| Error::Unknown(e.to_string()))?
+            .as_millis() as u64;
+        let url = format!(
+            "{}{}/correspond/1/{}",
+            self.protocol.get_prefix(),
+            self.www_host,
+            crate::credential::correspond_path(timestamp)?
+        );
+        let html = self.client.get(url).send().await?.text().await?;
+        let refresh_csrf = html
+            .split_once(r#"<div id="1-name">"#)
+            .and_then(|(_, rest)| rest.split_once("</div>"))
+            .map(|(csrf, _)| csrf.trim().to_owned())
+            .filter(|csrf| !csrf.is_empty())
+            .ok_or(Error::UnexpectedResp)?;
+
+        let url = format!(
+            "{}{}/x/passport-login/web/cookie/refresh",
+            self.protocol.get_prefix(),
+            self.passport_host
+        );
+        let resp = self
+            .client
+            .post(url)
+            .form(&[
+                ("csrf", old.bili_jct().as_str()),
+                ("refresh_csrf", refresh_csrf.as_str()),
+                ("source", "main_web"),
+                ("refresh_token", old.refresh_token().as_str()),
+            ])
+            .send()
+            .await?;
+        let cookies = resp
+            .cookies()
+            .map(|c| (c.name().to_owned(), c.value().to_owned()))
+            .collect::<Vec<_>>();
+        let refresh = resp.json::<PackInfo<Refresh>>().await?.as_result()?;
+        // DedeUserID is kept when not set again
+        let credential = Credential::from_pairs(
+            std::iter::once(("DedeUserID", old.dede_user_id().as_str()))
+                .chain(cookies.iter().map(|(k, v)| (k.as_str(), v.as_str()))),
+        )?
+        .with_refresh_token(refresh.refresh_token);
+        credential.require_login()?;
+        self.set_credential(credential.clone());
+        // the old refresh_token is gone once confirmed, keep the only valid one first
+        store.save(&credential)?;
+
+        // the old refresh_token stays valid until confirmed
+        let url = format!(
+            "{}{}/x/passport-login/web/confirm/refresh",
+            self.protocol.get_prefix(),
+            self.passport_host
+        );
+        self.client
+            .post(url)
+            .form(&[
+                ("csrf", credential.bili_jct().as_str()),
+                ("refresh_token", old.refresh_token().as_str()),
+            ])
+            .send()
+            .await?
+            .json::<PackInfo<serde_json::Value>>()
+            .await?
+            .check()?;
+        Ok(credential)
+    }
+
+    async fn refresh_if_needed<S>(self, store: &S) -> Result<bool>
+    where
+        S: CredentialStore + Sync,
+    {
+        if !self.get_cookie_info().await?.refresh() {
+            return Ok(false);
+        }
+        self.refresh_credential(store).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bili_mock::*;
+    use crate::prelude::*;
+
+    fn poll_body(code: i32, url: &str, refresh_token: &str) -> String {
+        format!(
+            r#"{{"code":0,"message":"0","data":{{"url":"{}","refresh_token":"{}","timestamp":0,"code":{},"message":""}}}}"#,
+            url, refresh_token, code
+        )
+    }
+
+    #[tokio::test]
+    async fn test_qrcode_login() -> anyhow::Result<()> {
+        let polls = std::sync::atomic::AtomicUsize::new(0);
+        let server = MockServer::start(move |req| match req.path.as_str() {
+            "/x/passport-login/web/qrcode/generate" => MockResponse::json(
+                r#"{"code":0,"message":"0","data":{"url":"https://account.bilibili.com/h5/account-h5/auth/scan-web?qrcode_key=key42","qrcode_key":"key42"}}"#,
+            ),
+            "/x/passport-login/web/qrcode/poll" if req.query_param("qrcode_key") == Some("key42") => {
+                match polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                    0 => MockResponse::json(poll_body(86101, "", "")),
+                    1 => MockResponse::json(poll_body(86090, "", "")),
+                    _ => MockResponse::json(poll_body(0, "https://passport.biligame.com/crossDomain?DedeUserID=42&SESSDATA=abc%2C123&bili_jct=jct", "token"))
+                        .header("Set-Cookie", "SESSDATA=abc%2C123; Path=/; Domain=bilibili.com; HttpOnly")
+                        .header("Set-Cookie", "bili_jct=jct; Path=/; Domain=bilibili.com")
+                        .header("Set-Cookie", "DedeUserID=42; Path=/; Domain=bilibili.com"),
+                }
+            }
+            _ => MockResponse::not_found(),
+        })
+        .await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .passport_host(server.host())
+            .build()?;
+
+        let qrcode = s.generate_qrcode().await?;
+        assert_eq!(qrcode.qrcode_key(), "key42");
+        assert_eq!(s.poll_qrcode(qrcode.qrcode_key()).await?, QrLoginState::Waiting);
+        assert_eq!(s.poll_qrcode(qrcode.qrcode_key()).await?, QrLoginState::Scanned);
+        let expect = Credential::new("abc%2C123".to_owned(), "jct".to_owned(), "42".to_owned())
+            .with_refresh_token("token".to_owned());
+        assert_eq!(
+            s.poll_qrcode(qrcode.qrcode_key()).await?,
+            QrLoginState::Confirmed(expect)
+        );
+        assert!(s.poll_qrcode("other").await.is_err());
+
+        let polls = server
+            .requests()
+            .into_iter()
+            .filter(|r| r.path.ends_with("/poll"))
+            .collect::<Vec<_>>();
+        assert_eq!(polls.len(), 4);
+        assert!(polls.iter().all(|r| r.header("host") == Some(server.host())));
+        Ok(())
+    }
+
+    /// a logged in `jct`/`token` account, `confirm` answers the confirm/refresh call
+    async fn refresh_server(confirm: &'static str) -> MockServer {
+        MockServer::start(move |req| match req.path.as_str() {
+            "/x/passport-login/web/cookie/info" if req.query_param("csrf") == Some("jct") => {
+                MockResponse::json(r#"{"code":0,"message":"0","data":{"refresh":true,"timestamp":1684466082000}}"#)
+            }
+            "/x/passport-login/web/cookie/info" => {
+                MockResponse::json(r#"{"code":0,"message":"0","data":{"refresh":false,"timestamp":1684466082000}}"#)
+            }
+            p if p.starts_with("/correspond/1/") => MockResponse::json(
+                r#"<html><body><div id="1-name">b0cc8411ded2f9db2cff2edb3123acac</div></body></html>"#,
+            ),
+            "/x/passport-login/web/cookie/refresh"
+                if req.method == "POST"
+                    && req.form_param("csrf") == Some("jct")
+                    && req.form_param("refresh_csrf") == Some("b0cc8411ded2f9db2cff2edb3123acac")
+                    && req.form_param("refresh_token") == Some("token") =>
+            {
+                MockResponse::json(r#"{"code":0,"message":"0","data":{"status":0,"message":"","refresh_token":"token2"}}"#)
+                    .header("Set-Cookie", "SESSDATA=new%2C456; Path=/; Domain=bilibili.com; HttpOnly")
+                    .header("Set-Cookie", "bili_jct=jct2; Path=/; Domain=bilibili.com")
+            }
+            "/x/passport-login/web/confirm/refresh"
+                if req.form_param("csrf") == Some("jct2")
+                    && req.form_param("refresh_token") == Some("token") =>
+            {
+                MockResponse::json(confirm)
+            }
+            _ => MockResponse::json(r#"{"code":-101,"message":"账号未登录"}"#),
+        })
+        .await
+    }
+
+    fn refresh_service(server: &MockServer) -> anyhow::Result<Service<'_>> {
+        let old = Credential::new("abc%2C123".to_owned(), "jct".to_owned(), "42".to_owned())
+            .with_refresh_token("token".to_owned());
+        Ok(Service::builder()
+            .protocol(Protocol::HTTP)
+            .passport_host(server.host())
+            .www_host(server.host())
+            .credential(old)
+            .build()?)
+    }
+
+    #[tokio::test]
+    async fn test_refresh_credential() -> anyhow::Result<()> {
+        let server = refresh_server(r#"{"code":0,"message":"0","ttl":1}"#).await;
+        let s = refresh_service(&server)?;
+
+        let dir = std::env::temp_dir().join(format!("bili-refresh-{}", std::process::id()));
+        let store = FileCredentialStore::new(dir.join("credential.json"));
+        assert!(s.refresh_if_needed(&store).await?);
+        let expect = Credential::new("new%2C456".to_owned(), "jct2".to_owned(), "42".to_owned())
+            .with_refresh_token("token2".to_owned());
+        assert_eq!(s.credential(), Some(expect.clone()));
+        assert_eq!(store.load()?, Some(expect));
+        // refreshed cookies need no more refresh
+        assert!(!s.refresh_if_needed(&store).await?);
+        std::fs::remove_dir_all(dir)?;
+
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .passport_host(server.host())
+            .build()?;
+        assert!(matches!(
+            s.refresh_credential(&store).await,
+            Err(Error::InvalidCredential(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_confirm_failed() -> anyhow::Result<()> {
+        let server = refresh_server(r#"{"code":-111,"message":"csrf 校验失败"}"#).await;
+        let s = refresh_service(&server)?;
+        let dir = std::env::temp_dir().join(format!("bili-confirm-{}", std::process::id()));
+        let store = FileCredentialStore::new(dir.join("credential.json"));
+        assert!(matches!(
+            s.refresh_credential(&store).await,
+            Err(Error::APIErr(-111, _))
+        ));
+        // the old refresh_token may be dead already, the new one must survive
+        let expect = Credential::new("new%2C456".to_owned(), "jct2".to_owned(), "42".to_owned())
+            .with_refresh_token("token2".to_owned());
+        assert_eq!(store.load()?, Some(expect));
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_qrcode_expired_and_url_cookies() -> anyhow::Result<()> {
+        let expired = std::sync::atomic::AtomicBool::new(true);
+        let server = MockServer::start(move |_| {
+            if expired.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                MockResponse::json(poll_body(86038, "", ""))
+            } else {
+                MockResponse::json(poll_body(
+                    0,
+                    "https://passport.biligame.com/crossDomain?DedeUserID=42&SESSDATA=abc%2C123&bili_jct=jct&gourl=x",
+                    "token",
+                ))
+            }
+        })
+        .await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .passport_host(server.host())
+            .build()?;
+        assert_eq!(s.poll_qrcode("key").await?, QrLoginState::Expired);
+        match s.poll_qrcode("key").await? {
+            QrLoginState::Confirmed(c) => {
+                assert_eq!(c.sessdata(), "abc%2C123");
+                assert_eq!(c.dede_user_id(), "42");
+                assert_eq!(c.refresh_token(), "token");
+            }
+            state => panic!("unexpected state {:?}", state),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+use super::*;
+
+#[derive(Default)]
+pub struct ServiceBuilder<'a> {
+    api_host: Option<&'a str>,
+    protocol: Option<Protocol>,
+    passport_host: Option<&'a str>,
+    www_host: Option<&'a str>,
+    user_agent: Option<&'a str>,
+    connect_timeout: Option<std::time::Duration>,
+    timeout: Option<std::time::Duration>,
+    read_timeout: Option<std::time::Duration>,
+    cookie_store: Option<std::sync::Arc<reqwest::cookie::Jar>>,
+    credential: Option<Credential>,
+    rate_limiter: Option<RateLimiter>,
+    proxy: Option<ProxyConfig>,
+    client: Option<reqwest::Client>,
+    middlewares: Vec<std::sync::Arc<dyn network_tools::Middleware>>,
+}
+
+impl<'a> ServiceBuilder<'a> {
+    /// host of the apis, `api.bilibili.com` by default
+    pub fn api_host(mut self, host: &'a str) -> Self {
+        self.api_host = Some(host);
+        self
+    }
+
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// host of the login apis, `passport.bilibili.com` by default
+    pub fn passport_host(mut self, host: &'a str) -> Self {
+        self.passport_host = Some(host);
+        self
+    }
+
+    /// host of web pages, `www.bilibili.com` by default
+    pub fn www_host(mut self, host: &'a str) -> Self {
+        self.www_host = Some(host);
+        self
+    }
+
+    /// `consts::USER_AGENT` by default
+    pub fn user_agent(mut self, user_agent: &'a str) -> Self {
+        self.user_agent = Some(user_agent);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// total time of a request, download chunks included
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// max wait for response headers and for every body read of downloads,
+    /// a stalled chunk fails with a retryable `TimedOut`
+    pub fn read_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// share a cookie jar, the credential is installed into it
+    pub fn cookie_store(mut self, jar: std::sync::Arc<reqwest::cookie::Jar>) -> Self {
+        self.cookie_store = Some(jar);
+        self
+    }
+
+    /// login cookies, installed into the client cookie jar
+    pub fn credential(mut self, credential: Credential) -> Self {
+        self.credential = Some(credential);
+        self
+    }
+
+    /// caps the bytes/sec of every download of the service, unlimited by default
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// proxies by host, route `consts::API_DOMAINS` and `consts::CDN_DOMAINS` separately if needed
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// use `client` as is, proxy and timeouts are left to it,
+    /// cookies are handled by a `Cookies` middleware instead
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// wraps every request after the default `Referer` and `User-Agent` are set
+    pub fn middleware(mut self, middleware: impl network_tools::Middleware) -> Self {
+        self.middlewares.push(std::sync::Arc::new(middleware));
+        self
+    }
+
+    /// record or replay every request, login secrets are scrubbed and wbi signs
+    /// are ignored so a cassette replays on any day
+    pub fn cassette(self, cassette: network_tools::Cassette) -> Self {
+        self.middleware(
+            cassette
+                .scrub_params(consts::SECRET_PARAMS)
+                .ignore_params(consts::VOLATILE_PARAMS),
+        )
+    }
+
+    pub fn build(self) -> Result<Service<'a>> {
+        let jar = self.cookie_store.unwrap_or_default();
+        if let Some(credential) = &self.credential {
+            install_cookies(&jar, credential);
+        }
+        let user_agent =
+            reqwest::header::HeaderValue::from_str(self.user_agent.unwrap_or(consts::USER_AGENT))
+                .map_err(|e| Error::Unknown(e.to_string()))?;
+        let headers = network_tools::DefaultHeaders::default()
+            .header(
+                reqwest::header::REFERER,
+                reqwest::header::HeaderValue::from_static(consts::REFERER),
+            )
+            .header(reqwest::header::USER_AGENT, user_agent);
+        let client = match self.client {
+            Some(inner) => {
+                network_tools::Client::builder(inner).with(network_tools::Cookies::new(jar.clone()))
+            }
+            None => {
+                let mut inner = reqwest::Client::builder().cookie_provider(jar.clone());
+                if let Some(proxy) = self.proxy {
+                    inner = proxy.apply(inner);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    inner = inner.connect_timeout(timeout);
+                }
+                if let Some(timeout) = self.timeout {
+                    inner = inner.timeout(timeout);
+                }
+                network_tools::Client::builder(inner.build()?)
+            }
+        };
+        let client = self
+            .middlewares
+            .into_iter()
+            .fold(client.with(headers), |client, middleware| {
+                client.with_arc(middleware)
+            })
+            .build();
+        Ok(Service {
+            api_host: self.api_host.unwrap_or(consts::HOST),
+            passport_host: self.passport_host.unwrap_or(consts::PASSPORT_HOST),
+            www_host: self.www_host.unwrap_or(consts::WWW_HOST),
+            protocol: self.protocol.unwrap_or(Protocol::HTTPS),
+            client,
+            read_timeout: self.read_timeout,
+            wbi: wbi::WbiCache::default(),
+            jar,
+            credential: std::sync::RwLock::new(self.credential),
+            rate_limiter: self.rate_limiter.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use bili_mock::*;
+
+    #[tokio::test]
+    async fn test_middleware() -> anyhow::Result<()> {
+        let server = MockServer::start(|_| {
+            MockResponse::json(
+                r#"{"code":0,"message":"0","data":{"url":"https://x","qrcode_key":"k"}}"#,
+            )
+        })
+        .await;
+        let metrics = network_tools::Metrics::default();
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .passport_host(server.host())
+            .middleware(metrics.clone())
+            .build()?;
+        assert_eq!(s.generate_qrcode().await?.qrcode_key(), "k");
+        assert_eq!(metrics.snapshot().requests, 1);
+        let req = &server.requests()[0];
+        assert_eq!(req.header("referer"), Some(consts::REFERER));
+        assert_eq!(req.header("user-agent"), Some(consts::USER_AGENT));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_proxy() -> anyhow::Result<()> {
+        let proxy = MockServer::start(|_| {
+            MockResponse::json(
+                r#"{"code":0,"message":"0","data":{"url":"https://x","qrcode_key":"k"}}"#,
+            )
+        })
+        .await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .proxy(ProxyConfig::default().route_all(
+                consts::API_DOMAINS,
+                Some(&format!("http://{}", proxy.host())),
+            )?)
+            .build()?;
+        assert_eq!(s.generate_qrcode().await?.qrcode_key(), "k");
+        // a forward proxy gets the absolute url
+        let req = &proxy.requests()[0];
+        assert_eq!(
+            req.path,
+            format!(
+                "http://{}/x/passport-login/web/qrcode/generate",
+                consts::PASSPORT_HOST
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_builder_options() -> anyhow::Result<()> {
+        use reqwest::cookie::CookieStore;
+
+        let server = MockServer::start(|_| {
+            MockResponse::json(r#"{"code":0,"message":"0","data":{"isLogin":true,"mid":42,"uname":"u","vipStatus":0}}"#)
+                .header("Set-Cookie", "buvid3=abc; Path=/")
+        })
+        .await;
+        let jar = std::sync::Arc::new(reqwest::cookie::Jar::default());
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .user_agent("dc-test")
+            .connect_timeout(std::time::Duration::from_secs(1))
+            .timeout(std::time::Duration::from_secs(5))
+            .cookie_store(jar.clone())
+            .credential(Credential::new(
+                "abc".to_owned(),
+                "jct".to_owned(),
+                "42".to_owned(),
+            ))
+            .build()?;
+        assert_eq!(*s.get_nav_info().await?.mid(), Some(42));
+        assert_eq!(server.requests()[0].header("user-agent"), Some("dc-test"));
+        // the shared jar got the credential and the new cookie
+        let bili = format!("https://{}", consts::HOST).parse()?;
+        let cookies = jar
+            .cookies(&bili)
+            .map(|v| v.to_str().unwrap_or_default().to_owned());
+        assert!(cookies.is_some_and(|c| c.contains("SESSDATA=abc")));
+        let local = format!("http://{}/", server.host()).parse()?;
+        assert!(jar.cookies(&local).is_some());
+
+        // a custom client keeps cookies through the middleware
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .client(reqwest::Client::new())
+            .build()?;
+        assert!(s.cookie_store().cookies(&local).is_none());
+        s.get_nav_info().await?;
+        assert!(s.cookie_store().cookies(&local).is_some());
+        s.get_nav_info().await?;
+        assert_eq!(
+            server.requests().last().and_then(|r| r.header("cookie")),
+            Some("buvid3=abc")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cassette() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("bili-cassette-{}.json", std::process::id()));
+        let id = VideoId::BVID("BV1Vh4y1v7qn".to_owned());
+        let server = bili_mock::FakeBili::start().await;
+        let host = server.host().to_owned();
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(&host)
+            .credential(Credential::from_cookie_str(
+                "SESSDATA=secret; bili_jct=jct",
+            )?)
+            .cassette(network_tools::Cassette::record(&path))
+            .build()?;
+        let cid = *s.get_basic_info(&id).await?.cid();
+        let title = s.get_music_info(&(id.clone(), cid)).await?.title().clone();
+        drop(server);
+        assert!(!tokio::fs::read_to_string(&path).await?.contains("secret"));
+
+        // the fake api is gone, and wbi signs of another time still match
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(&host)
+            .cassette(network_tools::Cassette::replay(&path).await?)
+            .build()?;
+        assert_eq!(*s.get_basic_info(&id).await?.cid(), cid);
+        assert_eq!(*s.get_music_info(&(id, cid)).await?.title(), title);
+        assert!(s.get_basic_info(&VideoId::AID(1)).await.is_err());
+        tokio::fs::remove_file(&path).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use self::prelude::DanmakuService;
+
+use super::*;
+
+impl<'a> DanmakuService for &Service<'a> {
+    // GET /x/v1/dm/list.so
+    async fn get_danmaku_xml(self, cid: u64) -> Result<Vec<Danmaku>> {
+        let url = format!(
+            "{}{}/x/v1/dm/list.so",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        let resp = self
+            .client
+            .get(url)
+            .query(&[("oid", cid.to_string())])
+            .send()
+            .await?;
+        if resp.status() != reqwest::StatusCode::OK {
+            return Err(Error::HttpStatus(resp.status().as_u16()));
+        }
+        let body = resp.bytes().await?;
+        // served as raw deflate whatever accept-encoding says
+        let xml = match body.first() {
+            Some(b'<') => body.to_vec(),
+            _ => miniz_oxide::inflate::decompress_to_vec(&body)
+                .map_err(|e| Error::InvalidDanmaku(format!("inflate failed: {:?}", e)))?,
+        };
+        Danmaku::from_xml(&String::from_utf8_lossy(&xml))
+    }
+
+    // GET /x/v2/dm/web/seg.so
+    async fn get_danmaku_segment(self, cid: u64, segment_index: u64) -> Result<Vec<Danmaku>> {
+        let url = format!(
+            "{}{}/x/v2/dm/web/seg.so",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        let query = [
+            ("type", "1".to_owned()),
+            ("oid", cid.to_string()),
+            ("segment_index", segment_index.to_string()),
+        ];
+        self.danmaku_segment(url, &query).await
+    }
+
+    async fn get_danmaku(self, cid: u64, duration: u64) -> Result<Vec<Danmaku>> {
+        let segments = duration.div_ceil(DANMAKU_SEGMENT_SECS).max(1);
+        let mut danmakus = vec![];
+        for index in 1..=segments {
+            danmakus.extend(self.get_danmaku_segment(cid, index).await?);
+        }
+        danmakus.sort_by_key(|d| *d.time_ms());
+        Ok(danmakus)
+    }
+
+    // GET /x/v2/dm/history/index
+    async fn get_danmaku_history_dates(self, cid: u64, month: &str) -> Result<Vec<String>> {
+        let url = format!(
+            "{}{}/x/v2/dm/history/index",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        let dates = self
+            .client
+            .get(url)
+            .query(&[
+                ("type", "1".to_owned()),
+                ("oid", cid.to_string()),
+                ("month", month.to_owned()),
+            ])
+            .send()
+            .await?
+            .json::<PackInfo<Vec<String>>>()
+            .await?
+            .into_optional()?;
+        Ok(dates.unwrap_or_default())
+    }
+
+    // GET /x/v2/dm/web/history/seg.so
+    async fn get_danmaku_history(self, cid: u64, date: &str) -> Result<Vec<Danmaku>> {
+        let url = format!(
+            "{}{}/x/v2/dm/web/history/seg.so",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        let query = [
+            ("type", "1".to_owned()),
+            ("oid", cid.to_string()),
+            ("date", date.to_owned()),
+        ];
+        self.danmaku_segment(url, &query).await
+    }
+}
+
+impl<'a> Service<'a> {
+    /// a `seg.so` like protobuf response
+    async fn danmaku_segment(&self, url: String, query: &[(&str, String)]) -> Result<Vec<Danmaku>> {
+        let resp = self.client.get(url).query(query).send().await?;
+        if resp.status() != reqwest::StatusCode::OK {
+            return Err(Error::HttpStatus(resp.status().as_u16()));
+        }
+        // errors come back as json instead of protobuf
+        let is_json = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("json"));
+        if is_json {
+            return resp
+                .json::<PackInfo<serde_json::Value>>()
+                .await?
+                .as_result()
+                .and(Err(Error::UnexpectedResp));
+        }
+        Danmaku::from_segment(&resp.bytes().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use bili_mock::{MockResponse, MockServer};
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?><i><chatid>1233524563</chatid><d p="1.5,1,25,16777215,1713355200,0,8b2a4f1c,1,10">第一</d><d p="400.25,5,25,16777215,1713355201,0,8b2a4f1c,2,10">第二</d></i>"#;
+
+    fn segment(index: u64) -> Vec<u8> {
+        let start = (index - 1) * DANMAKU_SEGMENT_SECS * 1000;
+        crate::danmaku::encode_segment(&[
+            Danmaku::new(start + 2000, DanmakuMode::Scroll, format!("{}-b", index)),
+            Danmaku::new(start + 1000, DanmakuMode::Top, format!("{}-a", index)),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_get_danmaku() -> anyhow::Result<()> {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/x/v1/dm/list.so" => MockResponse::bytes(
+                200,
+                miniz_oxide::deflate::compress_to_vec(XML.as_bytes(), 6),
+            ),
+            "/x/v2/dm/web/seg.so" => match req.query_param("segment_index") {
+                Some("3") => MockResponse::json(r#"{"code":-400,"message":"请求错误"}"#),
+                Some(index) => MockResponse::bytes(200, segment(index.parse().unwrap()))
+                    .header("content-type", "application/octet-stream"),
+                None => MockResponse::not_found(),
+            },
+            _ => MockResponse::not_found(),
+        })
+        .await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+
+        let xml = s.get_danmaku_xml(1233524563).await?;
+        assert_eq!(
+            xml.iter().map(|d| d.content().as_str()).collect::<Vec<_>>(),
+            vec!["第一", "第二"]
+        );
+        assert_eq!(*xml[1].time_ms(), 400250);
+
+        // 7 minutes, two segments
+        let danmakus = s.get_danmaku(1233524563, 420).await?;
+        assert_eq!(
+            danmakus
+                .iter()
+                .map(|d| d.content().as_str())
+                .collect::<Vec<_>>(),
+            vec!["1-a", "1-b", "2-a", "2-b"]
+        );
+        let req = server
+            .requests()
+            .into_iter()
+            .find(|r| r.path == "/x/v2/dm/web/seg.so")
+            .ok_or(anyhow::anyhow!("no request"))?;
+        assert_eq!(req.query_param("oid"), Some("1233524563"));
+        assert_eq!(req.query_param("type"), Some("1"));
+
+        assert!(matches!(
+            s.get_danmaku_segment(1233524563, 3).await,
+            Err(Error::APIErr(-400, _))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_danmaku_history() -> anyhow::Result<()> {
+        let server = MockServer::start(|req| {
+            let logged_in = req
+                .header("cookie")
+                .is_some_and(|c| c.contains("SESSDATA="));
+            match (req.path.as_str(), logged_in) {
+                (_, false) => MockResponse::json(r#"{"code":-101,"message":"账号未登录","ttl":1}"#),
+                ("/x/v2/dm/history/index", true) => match req.query_param("month") {
+                    Some("2024-04") => MockResponse::json(
+                        r#"{"code":0,"message":"0","ttl":1,"data":["2024-04-18","2024-04-20"]}"#,
+                    ),
+                    _ => MockResponse::json(r#"{"code":0,"message":"0","ttl":1,"data":null}"#),
+                },
+                ("/x/v2/dm/web/history/seg.so", true) => {
+                    let older = Danmaku::new(1000, DanmakuMode::Scroll, "旧").with_id(1);
+                    let newer = Danmaku::new(500, DanmakuMode::Scroll, "新").with_id(2);
+                    let snapshot = match req.query_param("date") {
+                        Some("2024-04-18") => vec![older],
+                        _ => vec![older, newer],
+                    };
+                    MockResponse::bytes(200, crate::danmaku::encode_segment(&snapshot))
+                }
+                _ => MockResponse::not_found(),
+            }
+        })
+        .await;
+
+        let anonymous = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        assert!(matches!(
+            anonymous.get_danmaku_history_dates(1, "2024-04").await,
+            Err(Error::APIErr(-101, _))
+        ));
+
+        // login cookies are installed for bilibili.com, log into the mock host instead
+        let jar = std::sync::Arc::new(reqwest::cookie::Jar::default());
+        jar.add_cookie_str("SESSDATA=x", &format!("http://{}/", server.host()).parse()?);
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .cookie_store(jar)
+            .build()?;
+        let dates = s.get_danmaku_history_dates(1, "2024-04").await?;
+        assert_eq!(dates, vec!["2024-04-18", "2024-04-20"]);
+        assert!(s.get_danmaku_history_dates(1, "2024-05").await?.is_empty());
+
+        let mut snapshots = vec![];
+        for date in &dates {
+            snapshots.extend(s.get_danmaku_history(1, date).await?);
+        }
+        assert_eq!(
+            Danmaku::merge(snapshots)
+                .iter()
+                .map(|d| d.content().as_str())
+                .collect::<Vec<_>>(),
+            vec!["新", "旧"]
+        );
+        Ok(())
+    }
+}
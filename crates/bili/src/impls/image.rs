@@ -0,0 +1,73 @@
+use self::prelude::ImageService;
+
+use super::*;
+
+impl<'a> ImageService for &Service<'a> {
+    // GET i0.hdslb.com/bfs/...@{w}w_{h}h.{format}
+    async fn get_image(self, url: &str, param: &ImageParam) -> Result<Image> {
+        let resp = self.client.get(param.url(url)).send().await?;
+        match resp.status().as_u16() {
+            200 => Ok(Image::new(resp.bytes().await?.to_vec())),
+            status => Err(Error::HttpStatus(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_image_url() -> anyhow::Result<()> {
+        let url = "https://i0.hdslb.com/bfs/archive/cover.jpg";
+        assert_eq!(ImageParam::default().url(url), url);
+        let param = ImageParamBuilder::default()
+            .width(320)
+            .height(180)
+            .format(ImageFormat::Webp)
+            .build()?;
+        assert_eq!(param.url(url), format!("{}@320w_180h_1c.webp", url));
+        // an existing suffix is replaced
+        let param = ImageParamBuilder::default().width(64).build()?;
+        assert_eq!(
+            param.url(&format!("{}@100w_100h", url)),
+            format!("{}@64w", url)
+        );
+        let param = ImageParamBuilder::default()
+            .format(ImageFormat::Png)
+            .build()?;
+        assert_eq!(param.url(url), format!("{}@.png", url));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_image() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let jpeg = [0xff, 0xd8, 0xff, 0xe0, 0, 0x10, b'J', b'F', b'I', b'F'];
+        let url = server.media("cover.jpg", jpeg.to_vec());
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+
+        let param = ImageParamBuilder::default()
+            .width(160)
+            .format(ImageFormat::Jpg)
+            .build()?;
+        let image = s.get_image(&url, &param).await?;
+        assert_eq!(*image.format(), Some(ImageFormat::Jpg));
+        assert_eq!(image.into_data(), jpeg);
+        assert_eq!(
+            server.requests().last().map(|r| r.path.clone()),
+            Some("/media/cover.jpg@160w.jpg".to_owned())
+        );
+
+        let missing = url.replace("cover", "missing");
+        assert!(matches!(
+            s.get_image(&missing, &ImageParam::default()).await,
+            Err(Error::HttpStatus(404))
+        ));
+        Ok(())
+    }
+}
@@ -1,25 +1,160 @@
+mod account;
+mod builder;
+mod danmaku;
+mod image;
 mod music;
 mod video;
+mod search;
 mod season;
+mod subtitle;
+mod user;
 
+pub use account::*;
+pub use builder::*;
+pub use danmaku::*;
+pub use image::*;
 pub use music::*;
 pub use video::*;
+pub use search::*;
 pub use season::*;
+pub use subtitle::*;
+pub use user::*;
 
 use super::*;
 
+// api codes returned when `w_rid` is rejected, keys probably rotated
+const WBI_REJECT_CODES: [i32; 2] = [-403, -352];
+
 pub struct Service<'a> {
     api_host: &'a str,
+    passport_host: &'a str,
+    www_host: &'a str,
     protocol: Protocol,
-    client: reqwest::Client,
+    client: network_tools::Client,
+    read_timeout: Option<std::time::Duration>,
+    wbi: wbi::WbiCache,
+    jar: std::sync::Arc<reqwest::cookie::Jar>,
+    credential: std::sync::RwLock<Option<Credential>>,
+    rate_limiter: RateLimiter,
 }
 
 impl<'a> Service<'a> {
+    /// talks to bilibili.com with default settings, see `Service::builder` for the rest
     pub fn new() -> Self {
-        Self {
-            api_host: consts::HOST,
-            protocol: Protocol::HTTPS,
-            client: reqwest::Client::new(),
+        Self::builder().build().expect("build default service failed")
+    }
+
+    pub fn builder() -> ServiceBuilder<'a> {
+        ServiceBuilder::default()
+    }
+
+    /// current login cookies, replaced after a refresh
+    pub fn credential(&self) -> Option<Credential> {
+        self.credential
+            .read()
+            .expect("credential lock poisoned")
+            .clone()
+    }
+
+    /// cookies of every request, the credential included
+    pub fn cookie_store(&self) -> &std::sync::Arc<reqwest::cookie::Jar> {
+        &self.jar
+    }
+
+    /// shared by all downloads, `set_rate` adjusts it at runtime
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    pub(crate) fn set_credential(&self, credential: Credential) {
+        install_cookies(&self.jar, &credential);
+        *self.credential.write().expect("credential lock poisoned") = Some(credential);
+    }
+}
+
+pub(crate) fn install_cookies(jar: &reqwest::cookie::Jar, credential: &Credential) {
+    let url = format!("https://{}", consts::HOST)
+        .parse::<reqwest::Url>()
+        .expect("valid host url");
+    for (name, value) in credential.cookies() {
+        jar.add_cookie_str(
+            &format!("{}={}; Domain={}; Path=/", name, value, COOKIE_DOMAIN),
+            &url,
+        );
+    }
+}
+
+impl<'a> Default for Service<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Service<'a> {
+    // GET /x/web-interface/nav
+    pub async fn wbi_keys(&self) -> Result<WbiKeys> {
+        if let Some(keys) = self.wbi.get().await {
+            return Ok(keys);
+        }
+
+        use serde::Deserialize;
+        #[derive(Debug, Deserialize)]
+        struct Nav {
+            wbi_img: WbiImg,
         }
+
+        #[derive(Debug, Deserialize)]
+        struct WbiImg {
+            img_url: String,
+            sub_url: String,
+        }
+
+        let url = format!(
+            "{}{}/x/web-interface/nav",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        // not login returns -101 with wbi_img still filled
+        let nav = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .json::<PackInfo<Nav>>()
+            .await?
+            .into_data()
+            .ok_or(Error::UnexpectedResp)?;
+        let keys = WbiKeys::from_urls(&nav.wbi_img.img_url, &nav.wbi_img.sub_url)
+            .ok_or(Error::UnexpectedResp)?;
+        self.wbi.set(keys.clone()).await;
+        Ok(keys)
     }
-}
\ No newline at end of file
+
+    /// GET a WBI-signed api, refetch keys and retry once if the sign is rejected
+    pub(crate) async fn get_wbi<T>(&self, url: String, query: &[(&str, String)]) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let mut retried = false;
+        loop {
+            let keys = self.wbi_keys().await?;
+            let query = keys.sign_now(query.iter().map(|(k, v)| (*k, v.clone())));
+            let res = self
+                .client
+                .get(&url)
+                .query(&query)
+                .send()
+                .await?
+                .json::<PackInfo<T>>()
+                .await?
+                .as_result();
+            match res {
+                Err(Error::APIErr(code, _)) if !retried && WBI_REJECT_CODES.contains(&code) => {
+                    self.wbi.invalidate().await;
+                    retried = true;
+                }
+                res => return res,
+            }
+        }
+    }
+}
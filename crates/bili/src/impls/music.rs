@@ -42,15 +42,7 @@ impl<'a> MusicService for &Service<'a> {
             music_title: String,
         }
 
-        let res = self
-            .client
-            .get(url)
-            .query(&query)
-            .send()
-            .await?
-            .json::<PackInfo<MusicInfoInner>>()
-            .await?
-            .as_result()?;
+        let res = self.get_wbi::<MusicInfoInner>(url, &query).await?;
         Ok(BasicMusicInfo {
             music_id: res.bgm_info.music_id,
             title: res.bgm_info.music_title,
@@ -69,11 +61,22 @@ mod tests {
     }
 
     async fn anyhow_music_info() -> anyhow::Result<()> {
-        let s = Service::new();
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
         let id = VideoId::BVID("BV1Vh4y1v7qn".to_owned());
         let basic_info = s.get_basic_info(&id).await?;
         let music_info = s.get_music_info(&(id, *basic_info.cid())).await?;
-        assert!(music_info.title == "一样的月光".to_owned());
+        assert!(music_info.title == "一样的月光");
+        // signed with the keys from nav
+        let req = server
+            .requests()
+            .into_iter()
+            .find(|r| r.path == "/x/player/wbi/v2")
+            .ok_or(anyhow::anyhow!("no request"))?;
+        assert!(req.query_param("w_rid").is_some());
         Ok(())
     }
 }
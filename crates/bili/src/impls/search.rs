@@ -0,0 +1,210 @@
+use futures::TryStreamExt;
+use serde::Deserialize;
+
+use self::prelude::SearchService;
+
+use super::*;
+
+impl<'a> SearchService for &Service<'a> {
+    // GET /x/web-interface/wbi/search/type
+    async fn search(self, param: &SearchParam) -> Result<SearchPage> {
+        self.ensure_buvid().await?;
+        let url = format!(
+            "{}{}/x/web-interface/wbi/search/type",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SearchInner {
+            page: u32,
+            pagesize: u32,
+            num_results: u64,
+            num_pages: u32,
+            // missing when nothing is found
+            #[serde(default)]
+            result: Vec<serde_json::Value>,
+        }
+
+        let res = self.get_wbi::<SearchInner>(url, &param.get_query()).await?;
+        let parse = |v: serde_json::Value| -> serde_json::Result<SearchResult> {
+            Ok(match param.search_type() {
+                SearchType::Video => SearchResult::Video(serde_json::from_value(v)?),
+                SearchType::User => SearchResult::User(serde_json::from_value(v)?),
+                SearchType::Bangumi => SearchResult::Bangumi(serde_json::from_value(v)?),
+                SearchType::LiveRoom => SearchResult::LiveRoom(serde_json::from_value(v)?),
+                SearchType::Article => SearchResult::Article(serde_json::from_value(v)?),
+            })
+        };
+        let results = res
+            .result
+            .into_iter()
+            .map(parse)
+            .collect::<serde_json::Result<Vec<_>>>()
+            .map_err(|e| Error::Unknown(format!("unexpected search result: {}", e)))?;
+        Ok(SearchPage::new(
+            res.page,
+            res.pagesize,
+            res.num_results,
+            res.num_pages,
+            results,
+        ))
+    }
+
+    fn search_stream(
+        self,
+        param: &SearchParam,
+    ) -> impl futures::Stream<Item = Result<SearchResult>> + Send {
+        futures::stream::try_unfold(Some(param.clone()), move |param| async move {
+            let Some(param) = param else {
+                return Ok::<_, Error>(None);
+            };
+            let page = self.search(&param).await?;
+            // an empty page ends it too, in case the count is off
+            let next = (*page.page() < *page.num_pages() && !page.results().is_empty())
+                .then(|| param.with_page(page.page() + 1));
+            let results =
+                futures::stream::iter(page.into_results().into_iter().map(Ok::<_, Error>));
+            Ok(Some((results, next)))
+        })
+        .try_flatten()
+    }
+}
+
+impl<'a> Service<'a> {
+    /// search and space listings answer -412 to clients without a `buvid3` cookie
+    // GET /x/frontend/finger/spi
+    pub(super) async fn ensure_buvid(&self) -> Result<()> {
+        use reqwest::cookie::CookieStore;
+
+        let api = format!("{}{}/", self.protocol.get_prefix(), self.api_host);
+        let api = api
+            .parse::<reqwest::Url>()
+            .map_err(|_| Error::UnexpectedResp)?;
+        let has_buvid = self
+            .jar
+            .cookies(&api)
+            .and_then(|v| v.to_str().ok().map(|v| v.contains("buvid3=")))
+            .unwrap_or(false);
+        if has_buvid {
+            return Ok(());
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Spi {
+            b_3: String,
+            b_4: String,
+        }
+
+        let spi = self
+            .client
+            .get(
+                api.join("x/frontend/finger/spi")
+                    .map_err(|_| Error::UnexpectedResp)?,
+            )
+            .send()
+            .await?
+            .json::<PackInfo<Spi>>()
+            .await?
+            .as_result()?;
+        self.jar
+            .add_cookie_str(&format!("buvid3={}; Path=/", spi.b_3), &api);
+        self.jar
+            .add_cookie_str(&format!("buvid4={}; Path=/", spi.b_4), &api);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[tokio::test]
+    async fn test_search() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let param = SearchParamBuilder::default()
+            .keyword("测评")
+            .order(SearchOrder::PubDate)
+            .duration(SearchDuration::Under10Min)
+            .tid(95)
+            .build()?;
+        let page = s.search(&param).await?;
+        assert_eq!((*page.num_results(), *page.num_pages()), (3, 2));
+        let SearchResult::Video(video) = &page.results()[0] else {
+            anyhow::bail!("not a video: {:?}", page.results()[0]);
+        };
+        assert_eq!(video.bvid(), "BV1qJ4m1Y71G");
+        assert_eq!(video.title(), "测评：一样的月光 & 其他");
+        assert_eq!(*video.duration(), 3723);
+        assert!(video.pic_url().starts_with("https://i0.hdslb.com/"));
+
+        let req = server
+            .requests()
+            .into_iter()
+            .find(|r| r.path == "/x/web-interface/wbi/search/type")
+            .ok_or(anyhow::anyhow!("no request"))?;
+        assert_eq!(req.query_param("order"), Some("pubdate"));
+        assert_eq!(req.query_param("duration"), Some("1"));
+        assert_eq!(req.query_param("tids"), Some("95"));
+        assert!(req.query_param("w_rid").is_some());
+        // buvid3 is fetched once
+        let spi = server
+            .requests()
+            .iter()
+            .filter(|r| r.path == "/x/frontend/finger/spi")
+            .count();
+        assert_eq!(spi, 1);
+
+        let all = s.search_stream(&param).try_collect::<Vec<_>>().await?;
+        let bvids = all
+            .iter()
+            .filter_map(|r| match r {
+                SearchResult::Video(v) => Some(v.bvid().as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(bvids, vec!["BV1qJ4m1Y71G", "BV1Vh4y1v7qn", "BV13m421J7fM"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_types() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let search = |search_type| {
+            let param = SearchParamBuilder::default()
+                .keyword("测评")
+                .search_type(search_type)
+                .build();
+            let s = &s;
+            async move { anyhow::Ok(s.search(&param?).await?.into_results().remove(0)) }
+        };
+
+        let SearchResult::User(user) = search(SearchType::User).await? else {
+            anyhow::bail!("not a user");
+        };
+        assert_eq!((user.name().as_str(), *user.fans()), ("数码测评君", 880000));
+        let SearchResult::Bangumi(bangumi) = search(SearchType::Bangumi).await? else {
+            anyhow::bail!("not a bangumi");
+        };
+        assert_eq!((*bangumi.season_id(), *bangumi.score()), (33378, Some(9.6)));
+        let SearchResult::LiveRoom(room) = search(SearchType::LiveRoom).await? else {
+            anyhow::bail!("not a live room");
+        };
+        assert_eq!((*room.room_id(), *room.is_live()), (21452505, true));
+        let SearchResult::Article(article) = search(SearchType::Article).await? else {
+            anyhow::bail!("not an article");
+        };
+        assert_eq!(article.title(), "一篇测评专栏");
+        Ok(())
+    }
+}
@@ -38,6 +38,10 @@ impl<'a> prelude::SeasonService for &Service<'a> {
             season_id: u64,
             #[serde(rename = "title")]
             season_name: String,
+            #[serde(default)]
+            intro: String,
+            #[serde(default)]
+            cover: String,
             #[serde(rename = "sections")]
             season_sections: Vec<SeasonSections>, // only 1 ??? fuck
         }
@@ -67,6 +71,8 @@ impl<'a> prelude::SeasonService for &Service<'a> {
             .await?
             .as_result()?;
         let season_name = detail.main_view.season_list.season_name;
+        let intro = detail.main_view.season_list.intro;
+        let cover_url = detail.main_view.season_list.cover;
         let season_id = detail.main_view.season_id;
         let owner = detail.main_view.owner;
         let sections = detail
@@ -81,6 +87,8 @@ impl<'a> prelude::SeasonService for &Service<'a> {
         Ok(SeasonListBuilder::default()
             .season_id(season_id)
             .season_name(season_name)
+            .intro(intro)
+            .cover_url(cover_url)
             .owner(owner)
             .sections(sections)
             .build()
@@ -122,29 +130,34 @@ mod tests {
 
     #[tokio::test]
     async fn test_season_list() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
         let (a, b) = tokio::join!(
-            anyhow_season_list_contains(),
-            anyhow_season_list_not_contains()
+            anyhow_season_list_contains(&s),
+            anyhow_season_list_not_contains(&s)
         );
         a?;
         b?;
         Ok(())
     }
 
-    async fn anyhow_season_list_contains() -> anyhow::Result<()> {
-        let s = Service::new();
+    async fn anyhow_season_list_contains(s: &Service<'_>) -> anyhow::Result<()> {
         let id = VideoId::BVID("BV13m421J7fM".to_owned());
         let season_id = s
             .season_id(&id)
             .await?
             .ok_or(anyhow::anyhow!("not season"))?;
         let list = s.get_video_relation_season_list(&id, season_id).await?;
-        assert!(list.sections().len() > 0);
+        assert!(!list.sections().is_empty());
+        assert_eq!(*list.season_id(), season_id);
+        assert!(list.cover_url().starts_with("https://"));
         Ok(())
     }
 
-    async fn anyhow_season_list_not_contains() -> anyhow::Result<()> {
-        let s = Service::new();
+    async fn anyhow_season_list_not_contains(s: &Service<'_>) -> anyhow::Result<()> {
         let id = VideoId::BVID("BV1nr421t7KX".to_owned());
         assert!(s.season_id(&id).await?.is_none());
         Ok(())
@@ -0,0 +1,115 @@
+use serde::Deserialize;
+
+use self::prelude::SubtitleService;
+
+use super::*;
+
+impl<'a> SubtitleService for &Service<'a> {
+    // GET /x/player/wbi/v2
+    async fn get_subtitle_tracks(self, id: &VideoId, cid: u64) -> Result<Vec<SubtitleTrack>> {
+        let url = format!(
+            "{}{}/x/player/wbi/v2",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        let query = [
+            match id {
+                VideoId::AID(aid) => ("aid", aid.to_string()),
+                VideoId::BVID(bvid) => ("bvid", bvid.clone()),
+            },
+            ("cid", cid.to_string()),
+        ];
+
+        #[derive(Debug, Deserialize)]
+        struct PlayerInner {
+            #[serde(default)]
+            subtitle: Option<SubtitleInner>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct SubtitleInner {
+            #[serde(default)]
+            subtitles: Vec<SubtitleTrackInner>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct SubtitleTrackInner {
+            id: u64,
+            lan: String,
+            lan_doc: String,
+            subtitle_url: String,
+            #[serde(default)]
+            ai_type: u8,
+        }
+
+        let res = self.get_wbi::<PlayerInner>(url, &query).await?;
+        let mut tracks = res
+            .subtitle
+            .map(|s| s.subtitles)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| {
+                // scheme-relative on the real cdn
+                let url = if t.subtitle_url.starts_with("//") {
+                    format!("https:{}", t.subtitle_url)
+                } else {
+                    t.subtitle_url
+                };
+                SubtitleTrack::new(t.id, t.lan, t.lan_doc, url, t.ai_type != 0)
+            })
+            .collect::<Vec<_>>();
+        tracks.sort_by_key(|t| *t.is_ai());
+        Ok(tracks)
+    }
+
+    async fn get_subtitle(self, track: &SubtitleTrack) -> Result<Subtitle> {
+        if track.url().is_empty() {
+            return Err(Error::UnexpectedResp);
+        }
+        let resp = self.client.get(track.url()).send().await?;
+        match resp.status().as_u16() {
+            200 => Ok(resp.json::<Subtitle>().await?),
+            status => Err(Error::HttpStatus(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[tokio::test]
+    async fn test_get_subtitles() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let id = VideoId::BVID("BV1Vh4y1v7qn".to_owned());
+        let tracks = s.get_subtitle_tracks(&id, 1233524563).await?;
+        assert_eq!(
+            tracks
+                .iter()
+                .map(|t| (t.lang().as_str(), *t.is_ai()))
+                .collect::<Vec<_>>(),
+            vec![("zh-CN", false), ("ai-en", true)]
+        );
+
+        let subtitle = s.get_subtitle(&tracks[0]).await?;
+        assert_eq!(subtitle.body().len(), 3);
+        assert_eq!(subtitle.body()[1].content(), "徘徊在这条街上\n{一样的}夜晚");
+        assert!(subtitle
+            .to_srt()
+            .starts_with("1\n00:00:00,500 --> 00:00:02,800\n一样的月光\n"));
+
+        // videos without subtitles have an empty list
+        let id = VideoId::BVID("BV1qJ4m1Y71G".to_owned());
+        server.fixture(
+            "player_v2/BV1qJ4m1Y71G",
+            r#"{"code":0,"message":"0","ttl":1,"data":{"subtitle":{"subtitles":[]}}}"#,
+        );
+        assert!(s.get_subtitle_tracks(&id, 1).await?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use futures::TryStreamExt;
+use serde::Deserialize;
+
+use self::prelude::UserService;
+
+use super::*;
+
+impl<'a> UserService for &Service<'a> {
+    // GET /x/space/wbi/arc/search
+    async fn get_user_videos(self, param: &UserVideoParam) -> Result<UserVideoPage> {
+        self.ensure_buvid().await?;
+        let url = format!(
+            "{}{}/x/space/wbi/arc/search",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+
+        #[derive(Debug, Deserialize)]
+        struct VideoList {
+            #[serde(default)]
+            vlist: Vec<UserVideo>,
+        }
+        #[derive(Debug, Deserialize)]
+        struct PageInner {
+            pn: u32,
+            ps: u32,
+            count: u64,
+        }
+        #[derive(Debug, Deserialize)]
+        struct ArcSearchInner {
+            list: VideoList,
+            page: PageInner,
+        }
+
+        let res = self
+            .get_wbi::<ArcSearchInner>(url, &param.get_query())
+            .await?;
+        Ok(UserVideoPage::new(
+            res.page.pn,
+            res.page.ps,
+            res.page.count,
+            res.list.vlist,
+        ))
+    }
+
+    fn user_videos_stream(
+        self,
+        param: &UserVideoParam,
+    ) -> impl futures::Stream<Item = Result<UserVideo>> + Send {
+        futures::stream::try_unfold(Some(param.clone()), move |param| async move {
+            let Some(param) = param else {
+                return Ok::<_, Error>(None);
+            };
+            let page = self.get_user_videos(&param).await?;
+            let seen = *page.page() as u64 * *page.page_size() as u64;
+            // an empty page ends it too, in case the count is off
+            let next = (seen < *page.count() && !page.videos().is_empty())
+                .then(|| param.with_page(page.page() + 1));
+            let videos = futures::stream::iter(page.into_videos().into_iter().map(Ok::<_, Error>));
+            Ok(Some((videos, next)))
+        })
+        .try_flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[tokio::test]
+    async fn test_get_user_videos() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let param = UserVideoParamBuilder::default()
+            .mid(1234567)
+            .order(UserVideoOrder::Click)
+            .keyword("测评")
+            .page_size(2)
+            .build()?;
+        let page = s.get_user_videos(&param).await?;
+        assert_eq!((*page.page(), *page.count()), (1, 3));
+        let video = &page.videos()[0];
+        assert_eq!(video.bvid(), "BV1qJ4m1Y71G");
+        assert_eq!(video.author(), "数码测评君");
+        assert_eq!((*video.play(), *video.duration()), (123456, 12));
+        assert!(video.pic_url().starts_with("http://i0.hdslb.com/"));
+        // hidden play count
+        assert_eq!(*page.videos()[1].play(), 0);
+        assert_eq!(*page.videos()[1].duration(), 3825);
+
+        let req = server
+            .requests()
+            .into_iter()
+            .find(|r| r.path == "/x/space/wbi/arc/search")
+            .ok_or(anyhow::anyhow!("no request"))?;
+        assert_eq!(req.query_param("order"), Some("click"));
+        assert_eq!(req.query_param("keyword"), Some("测评"));
+        assert_eq!(req.query_param("ps"), Some("2"));
+        assert!(req.query_param("w_rid").is_some());
+
+        let all = s.user_videos_stream(&param).try_collect::<Vec<_>>().await?;
+        let bvids = all.iter().map(|v| v.bvid().as_str()).collect::<Vec<_>>();
+        assert_eq!(bvids, vec!["BV1qJ4m1Y71G", "BV1Vh4y1v7qn", "BV13m421J7fM"]);
+
+        let unknown = UserVideoParamBuilder::default().mid(1).build()?;
+        assert!(s.get_user_videos(&unknown).await.is_err());
+        Ok(())
+    }
+}
@@ -1,8 +1,58 @@
 use super::*;
 
+/// a chunk response must be `206` covering exactly `range` of a `size` bytes file,
+/// anything else (error pages, ignored ranges) would corrupt the output
+fn check_range(
+    resp: &reqwest::Response,
+    range: &std::ops::RangeInclusive<u64>,
+    size: u64,
+) -> Result<()> {
+    let (start, end) = (*range.start(), *range.end());
+    match resp.status().as_u16() {
+        206 => {}
+        // fine only when the whole file is asked
+        200 if start == 0 && end + 1 == size => return Ok(()),
+        200 => return Err(Error::RangeIgnored(start, end)),
+        status => return Err(Error::HttpStatus(status)),
+    }
+    let content_range = resp
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok());
+    match content_range.and_then(parse_content_range) {
+        Some(got) if got == (start, end, size) => Ok(()),
+        _ => Err(Error::ContentRangeMismatch(
+            start,
+            end,
+            size,
+            content_range.map(str::to_owned),
+        )),
+    }
+}
+
+/// `bytes {start}-{end}/{size}`
+fn parse_content_range(v: &str) -> Option<(u64, u64, u64)> {
+    let (range, size) = v.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    Some((
+        start.trim().parse().ok()?,
+        end.trim().parse().ok()?,
+        size.trim().parse().ok()?,
+    ))
+}
+
 impl<'a> prelude::VideoService for &Service<'a> {
-    // GET /x/player/pagelist
     async fn get_basic_info(self, id: &VideoId) -> Result<VideoMetadata> {
+        self.get_pages(id)
+            .await?
+            .into_iter()
+            .nth(0)
+            .map(VideoMetadata::from)
+            .ok_or(Error::UnexpectedResp)
+    }
+
+    // GET /x/player/pagelist
+    async fn get_pages(self, id: &VideoId) -> Result<Vec<VideoPage>> {
         let url = format!(
             "{}{}/x/player/pagelist",
             self.protocol.get_prefix(),
@@ -18,10 +68,46 @@ impl<'a> prelude::VideoService for &Service<'a> {
             .query(&query)
             .send()
             .await?
-            .json::<PackInfo<Vec<VideoMetadata>>>()
+            .json::<PackInfo<Vec<VideoPage>>>()
+            .await?
+            .as_result()?;
+        if res.is_empty() {
+            Err(Error::UnexpectedResp)
+        } else {
+            Ok(res)
+        }
+    }
+
+    // GET /x/web-interface/view/detail, the view with tags in one request
+    async fn get_video_detail(self, id: &VideoId) -> Result<VideoDetail> {
+        use serde::Deserialize;
+        #[derive(Debug, Deserialize)]
+        struct Detail {
+            #[serde(rename = "View")]
+            view: VideoDetail,
+            #[serde(rename = "Tags", default)]
+            tags: Option<Vec<Tag>>,
+        }
+
+        let url = format!(
+            "{}{}/x/web-interface/view/detail",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        let query = match id {
+            VideoId::AID(aid) => [("aid", aid.to_string())],
+            VideoId::BVID(bvid) => [("bvid", bvid.clone())],
+        };
+        let detail = self
+            .client
+            .get(url)
+            .query(&query)
+            .send()
+            .await?
+            .json::<PackInfo<Detail>>()
             .await?
             .as_result()?;
-        res.into_iter().nth(0).ok_or(Error::UnexpectedResp)
+        Ok(detail.view.with_tags(detail.tags.unwrap_or_default()))
     }
 
     // GET /x/player/playurl
@@ -46,62 +132,124 @@ impl<'a> prelude::VideoService for &Service<'a> {
         }
     }
 
-    async fn download<'b, W>(self, param: &DownloadParam, mut writer: W) -> Result<()>
+    // GET /x/player/playurl with fnval=16
+    async fn get_dash_info(self, param: &GetDownloadInfoParam) -> Result<DashInfo> {
+        use serde::Deserialize;
+        #[derive(Debug, Deserialize)]
+        struct DashResp {
+            dash: Option<DashInfo>,
+        }
+
+        let url = format!(
+            "{}{}/x/player/playurl",
+            self.protocol.get_prefix(),
+            self.api_host
+        );
+        let res = self
+            .client
+            .get(url)
+            .query(&param.get_dash_query())
+            .send()
+            .await?
+            .json::<PackInfo<DashResp>>()
+            .await?
+            .as_result()?;
+        res.dash.ok_or(Error::UnexpectedResp)
+    }
+
+    async fn get_track_info(self, stream: &DashStream) -> Result<DurlInfo> {
+        // dash api has no size, ask cdn with a 1 byte range
+        let resp = self
+            .client
+            .get(stream.base_url())
+            .header("Range", "bytes=0-0")
+            .send()
+            .await?;
+        let content_range = resp
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok());
+        let size = match (resp.status().as_u16(), content_range.and_then(parse_content_range)) {
+            (206, Some((0, 0, size))) => size,
+            (206, _) => return Err(Error::UnexpectedResp),
+            (200, _) => return Err(Error::RangeIgnored(0, 0)),
+            (status, _) => return Err(Error::HttpStatus(status)),
+        };
+        Ok(DurlInfo::new(size, stream.base_url().clone())
+            .with_backup_url(stream.backup_url().clone().unwrap_or_default()))
+    }
+
+    async fn download<W>(self, param: &DownloadParam, mut writer: W) -> Result<()>
     where
         W: tokio::io::AsyncWriteExt + tokio::io::AsyncSeekExt + Send + Sync + Unpin,
     {
-        use futures::TryStreamExt;
-
         let durl_info = param.info();
         let size = *durl_info.size();
 
         // --- prepare chan
         let pool_size = param.conn_pool().unwrap_or(1);
-        let (tx, mut rx) = tokio::sync::mpsc::channel(pool_size as usize);
+        // pieces as they arrive, memory stays a few reads per connection
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<Piece>(pool_size as usize * 4);
         let chunk_size = param.chunk_size().unwrap_or(*durl_info.size());
-        let mut start = 0;
+        let mut journal = match param.journal() {
+            Some(path) => Journal::load(path, size, chunk_size).await,
+            None => Journal::new(size, chunk_size),
+        };
+        let progress = Progress(param.progress().clone());
+        progress.emit(DownloadEvent::Started {
+            size,
+            finished: journal.finished(),
+        });
         let mut fg = tokio::task::JoinSet::new();
 
         // --- start chunk download
-        while start < size {
-            let end = (start + chunk_size).min(size - 1);
-            let url = durl_info.url().clone();
+        let urls = std::sync::Arc::new(durl_info.urls());
+        let retry = param.retry().clone().unwrap_or_default();
+        let permits = std::sync::Arc::new(tokio::sync::Semaphore::new(pool_size as usize));
+        let fetcher = Fetcher {
+            client: self.client.clone(),
+            size,
+            progress: progress.clone(),
+            limiter: self.rate_limiter.clone(),
+            read_timeout: self.read_timeout,
+        };
+        for range in journal.pending() {
+            let urls = urls.clone();
+            let retry = retry.clone();
+            let permits = permits.clone();
             let txc = tx.clone();
-            let client = self.client.clone();
-            async fn do_download<Range: std::ops::RangeBounds<u64>>(
-                client: &reqwest::Client,
-                url: String,
-                range: Range,
-                tx: tokio::sync::mpsc::Sender<(Range, reqwest::Response)>,
-            ) -> Result<()> {
-                let resp = client
-                    .get(url)
-                    .header("Referer", "https://www.bilibili.com")
-                    .header("Range", {
-                        let mut s = String::from("bytes=");
-                        let start = match range.start_bound() {
-                            std::ops::Bound::Included(&x) => x,
-                            std::ops::Bound::Excluded(&x) => x + 1,
-                            std::ops::Bound::Unbounded => 0,
-                        };
-                        s.push_str(&start.to_string());
-                        s.push('-');
-                        match range.end_bound() {
-                            std::ops::Bound::Included(&x) => s.push_str(&x.to_string()),
-                            std::ops::Bound::Excluded(&x) => s.push_str(&(x - 1).to_string()),
-                            std::ops::Bound::Unbounded => {},
-                        };
-                        s
-                    })
-                    .header("User-Agent", "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36 Edg/122.0.0.0")
-                    .send()
-                    .await?;
-                tx.send((range, resp))
+            let fetcher = fetcher.clone();
+            fg.spawn(async move {
+                // at most `conn_pool` connections at a time
+                let _permit = permits
+                    .acquire_owned()
+                    .await
+                    .map_err(|e| Error::FutureErr(e.to_string()))?;
+                fetcher.progress.emit(DownloadEvent::ChunkStarted {
+                    range: range.clone(),
+                });
+                // bytes are written as received, a retry resumes after them
+                let mut attempt = 0;
+                let mut received = 0;
+                loop {
+                    let url = &urls[attempt as usize % urls.len()];
+                    match fetcher.fetch(url, &range, &mut received, &txc).await {
+                        Err(e) if e.is_retryable() && attempt < *retry.max_retries() => {
+                            attempt += 1;
+                            fetcher.progress.emit(DownloadEvent::ChunkRetried {
+                                range: range.clone(),
+                                attempt,
+                                error: e.to_string(),
+                            });
+                            tokio::time::sleep(retry.delay(attempt - 1)).await;
+                        }
+                        res => break res?,
+                    }
+                }
+                txc.send(Piece::Done(range))
                     .await
                     .map_err(|e| Error::ChannelError(e.to_string()))
-            }
-            fg.spawn(async move { do_download(&client, url, start..=end, txc).await });
-            start += chunk_size;
+            });
         }
 
         // --- stop
@@ -114,57 +262,614 @@ impl<'a> prelude::VideoService for &Service<'a> {
         });
 
         // --- block recv
-        while let Some((range, resp)) = rx.recv().await {
-            let _seek = writer
-                .seek(std::io::SeekFrom::Start(*range.start()))
-                .await?;
-            let stream = resp.bytes_stream().map_err(std::io::Error::other);
-            let mut stream = tokio_util::io::StreamReader::new(stream);
-            tokio::io::copy(&mut stream, &mut writer).await?;
+        while let Some(piece) = rx.recv().await {
+            let range = match piece {
+                Piece::Bytes(offset, bytes) => {
+                    writer.seek(std::io::SeekFrom::Start(offset)).await?;
+                    writer.write_all(&bytes).await?;
+                    continue;
+                }
+                Piece::Done(range) => range,
+            };
+            if let Some(path) = param.journal() {
+                // handed to the os before it is recorded, which survives a killed
+                // process but not a power loss, `W` has no way to sync
+                writer.flush().await?;
+                journal.mark(&range);
+                journal.save(path).await?;
+            }
+            progress.emit(DownloadEvent::ChunkFinished { range });
         }
         writer.flush().await?;
 
         stop.await.map_err(|e| Error::FutureErr(e.to_string()))??;
+
+        let written = writer.seek(std::io::SeekFrom::End(0)).await?;
+        if written != size {
+            return Err(Error::SizeMismatch(size, written));
+        }
+        if let Some(path) = param.journal() {
+            match tokio::fs::remove_file(path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        progress.emit(DownloadEvent::Finished);
         Ok(())
     }
 }
 
+/// what a chunk download hands to the writer, in order for each chunk
+enum Piece {
+    /// bytes at this offset of the file
+    Bytes(u64, bytes::Bytes),
+    /// every byte of the chunk was sent before
+    Done(std::ops::RangeInclusive<u64>),
+}
+
+/// downloads one chunk of a file
+#[derive(Clone)]
+struct Fetcher {
+    client: network_tools::Client,
+    size: u64,
+    progress: Progress,
+    limiter: RateLimiter,
+    read_timeout: Option<std::time::Duration>,
+}
+
+impl Fetcher {
+    /// send the rest of `range` after the `received` bytes of earlier attempts
+    async fn fetch(
+        &self,
+        url: &str,
+        range: &std::ops::RangeInclusive<u64>,
+        received: &mut u64,
+        tx: &tokio::sync::mpsc::Sender<Piece>,
+    ) -> Result<()> {
+        let expect = range.end() - range.start() + 1;
+        let rest = range.start() + *received..=*range.end();
+        let req = self
+            .client
+            .get(url)
+            .header("Range", format!("bytes={}-{}", rest.start(), rest.end()))
+            .send();
+        let mut resp = self.within(req).await??;
+        check_range(&resp, &rest, self.size)?;
+        while let Some(mut bytes) = self.within(resp.chunk()).await?? {
+            // never spill into the next chunk, the range was checked already
+            bytes.truncate((expect - *received) as usize);
+            if bytes.is_empty() {
+                break;
+            }
+            let len = bytes.len() as u64;
+            self.limiter.acquire(len).await;
+            tx.send(Piece::Bytes(range.start() + *received, bytes))
+                .await
+                .map_err(|e| Error::ChannelError(e.to_string()))?;
+            *received += len;
+            self.progress.emit(DownloadEvent::Bytes(len));
+        }
+        if *received == expect {
+            Ok(())
+        } else {
+            Err(Error::SizeMismatch(expect, *received))
+        }
+    }
+
+    async fn within<F: std::future::Future>(&self, f: F) -> Result<F::Output> {
+        match self.read_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, f).await.map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "read timeout").into()
+            }),
+            None => Ok(f.await),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Progress(Option<tokio::sync::mpsc::UnboundedSender<DownloadEvent>>);
+
+impl Progress {
+    fn emit(&self, event: DownloadEvent) {
+        if let Some(tx) = &self.0 {
+            let _ = tx.send(event);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::prelude::*;
     use super::*;
+    use bili_mock::serve_range;
 
     #[test]
-    fn test_get_basic_info() -> anyhow::Result<()> {
-        let rt = tokio::runtime::Runtime::new()?;
-        rt.block_on(anyhow_get_basic_info())?;
+    fn test_dash_select() -> anyhow::Result<()> {
+        let info = serde_json::from_str::<PackInfo<DashInfo>>(
+            r#"{"code":0,"message":"0","data":{
+                "duration": 120,
+                "video": [
+                    {"id":80,"codecid":7,"codecs":"avc1.640032","bandwidth":1000,"width":1920,"height":1080,"frame_rate":"30","base_url":"https://cdn/80-7.m4s","backup_url":["https://bak/80-7.m4s"],"mime_type":"video/mp4","segment_base":{"initialization":"0-900","index_range":"901-1200"}},
+                    {"id":80,"codecid":12,"codecs":"hev1.1.6.L150.90","bandwidth":800,"width":1920,"height":1080,"frame_rate":"30","baseUrl":"https://cdn/80-12.m4s","backupUrl":null,"mimeType":"video/mp4","SegmentBase":{"Initialization":"0-900","indexRange":"901-1200"}},
+                    {"id":32,"codecid":7,"codecs":"avc1.64001F","bandwidth":400,"width":852,"height":480,"frame_rate":"30","base_url":"https://cdn/32-7.m4s","backup_url":[],"mime_type":"video/mp4","segment_base":{"initialization":"0-900","index_range":"901-1200"}}
+                ],
+                "audio": [
+                    {"id":30216,"codecid":0,"codecs":"mp4a.40.2","bandwidth":60000,"base_url":"https://cdn/30216.m4s","backup_url":[],"mime_type":"audio/mp4","segment_base":{"initialization":"0-800","index_range":"801-1000"}},
+                    {"id":30280,"codecid":0,"codecs":"mp4a.40.2","bandwidth":190000,"base_url":"https://cdn/30280.m4s","backup_url":[],"mime_type":"audio/mp4","segment_base":{"initialization":"0-800","index_range":"801-1000"}}
+                ]
+            }}"#,
+        )?
+        .as_result()?;
+
+        let best = info.select_video(&DashSelector::default());
+        assert_eq!(best.map(|v| v.base_url().as_str()), Some("https://cdn/80-7.m4s"));
+        assert_eq!(
+            best.and_then(|v| v.backup_url().clone()),
+            Some(vec!["https://bak/80-7.m4s".to_owned()])
+        );
+
+        let hevc = DashSelectorBuilder::default()
+            .codecs(vec![VideoCodec::HEVC, VideoCodec::AVC])
+            .build()?;
+        let best = info.select_video(&hevc);
+        assert_eq!(best.map(|v| *v.codecid()), Some(12));
+
+        let low = DashSelectorBuilder::default().max_quality(Some(64)).build()?;
+        let best = info.select_video(&low);
+        assert_eq!(best.map(|v| (*v.id(), *v.height())), Some((32, 480)));
+
+        let av1 = DashSelectorBuilder::default()
+            .codecs(vec![VideoCodec::AV1])
+            .build()?;
+        assert!(info.select_video(&av1).is_none());
+
+        let audio = info.select_audio(&DashSelector::default());
+        assert_eq!(audio.map(|a| *a.id()), Some(30280));
+        let audio = info.select_audio(
+            &DashSelectorBuilder::default()
+                .max_audio_quality(Some(30232))
+                .build()?,
+        );
+        assert_eq!(audio.map(|a| *a.id()), Some(30216));
         Ok(())
     }
 
-    async fn anyhow_get_basic_info() -> anyhow::Result<()> {
+    #[tokio::test]
+    async fn test_download_resume() -> anyhow::Result<()> {
+        const BODY: &[u8] = b"0123456789abcdefghij";
+        let server = bili_mock::MockServer::start(serve_range(BODY)).await;
         let s = Service::new();
-        let basic_info = s
-            .get_basic_info(&VideoId::BVID("BV1qJ4m1Y71G".to_owned()))
+        let dir = std::env::temp_dir().join(format!("bili-resume-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await?;
+        let target = dir.join("track.m4s");
+        let journal = Journal::path_for(&target);
+
+        // first run was interrupted after chunks 0 and 2
+        let mut partial = Journal::new(BODY.len() as u64, 8);
+        partial.mark(&(0..=7));
+        partial.mark(&(16..=19));
+        partial.save(&journal).await?;
+        let mut content = BODY.to_vec();
+        content[8..16].fill(b'-');
+        tokio::fs::write(&target, &content).await?;
+
+        let param = DownloadParam {
+            info: DurlInfo::new(BODY.len() as u64, format!("http://{}/track.m4s", server.host())),
+            chunk_size: Some(8),
+            conn_pool: Some(2),
+            journal: Some(journal.clone()),
+            retry: None,
+            progress: None,
+        };
+        let mut f = tokio::fs::OpenOptions::new().write(true).open(&target).await?;
+        s.download(&param, &mut f).await?;
+        assert_eq!(tokio::fs::read(&target).await?, BODY);
+        assert!(!journal.exists());
+        let ranges = server
+            .requests()
+            .iter()
+            .filter_map(|r| r.header("range").map(str::to_owned))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec!["bytes=8-15".to_owned()]);
+
+        // a longer leftover file is reported
+        tokio::fs::write(&target, b"0123456789abcdefghijklmn").await?;
+        let mut f = tokio::fs::OpenOptions::new().write(true).open(&target).await?;
+        let param = DownloadParam { journal: None, ..param };
+        assert!(matches!(
+            s.download(&param, &mut f).await,
+            Err(Error::SizeMismatch(20, 24))
+        ));
+
+        tokio::fs::remove_dir_all(dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_retry_backup() -> anyhow::Result<()> {
+        const BODY: &[u8] = b"0123456789abcdefghij";
+        let short = std::sync::atomic::AtomicBool::new(true);
+        let range = serve_range(BODY);
+        let server = bili_mock::MockServer::start(move |req| match req.path.as_str() {
+            "/primary.m4s" => bili_mock::MockResponse {
+                status: 502,
+                headers: vec![],
+                body: vec![],
+            },
+            "/backup.m4s" => {
+                let mut resp = range(req);
+                // cut the first body short
+                if short.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                    resp.body.pop();
+                }
+                resp
+            }
+            _ => bili_mock::MockResponse::not_found(),
+        })
+        .await;
+        let s = Service::new();
+        let policy = RetryPolicyBuilder::default()
+            .base_delay(std::time::Duration::from_millis(1))
+            .build()?;
+        let param = DownloadParam {
+            info: DurlInfo::new(BODY.len() as u64, format!("http://{}/primary.m4s", server.host()))
+                .with_backup_url(vec![format!("http://{}/backup.m4s", server.host())]),
+            chunk_size: Some(BODY.len() as u64),
+            conn_pool: Some(1),
+            journal: None,
+            retry: Some(policy.clone()),
+            progress: None,
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut out = std::io::Cursor::new(vec![]);
+        s.download(
+            &DownloadParam {
+                progress: Some(tx),
+                ..param.clone()
+            },
+            &mut out,
+        )
+        .await?;
+        assert_eq!(out.into_inner(), BODY);
+        let mut events = vec![];
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        assert_eq!(events.first(), Some(&DownloadEvent::Started { size: 20, finished: 0 }));
+        assert_eq!(events.last(), Some(&DownloadEvent::Finished));
+        let (mut bytes, mut retries) = (0, vec![]);
+        for event in events.iter() {
+            match event {
+                DownloadEvent::Bytes(n) => bytes += n,
+                DownloadEvent::ChunkRetried { attempt, .. } => retries.push(*attempt),
+                _ => {}
+            }
+        }
+        assert_eq!(bytes, 20);
+        assert_eq!(retries, vec![1, 2, 3]);
+        // primary, short backup, primary, backup resuming after the 19 bytes
+        let paths = server
+            .requests()
+            .into_iter()
+            .map(|r| (r.path.clone(), r.header("range").unwrap_or_default().to_owned()))
+            .collect::<Vec<_>>();
+        let expect = [
+            ("/primary.m4s", "bytes=0-19"),
+            ("/backup.m4s", "bytes=0-19"),
+            ("/primary.m4s", "bytes=19-19"),
+            ("/backup.m4s", "bytes=19-19"),
+        ];
+        assert_eq!(paths, expect.map(|(p, r)| (p.to_owned(), r.to_owned())));
+
+        // 404 is not retried
+        let param = DownloadParam {
+            info: DurlInfo::new(BODY.len() as u64, format!("http://{}/gone.m4s", server.host())),
+            ..param
+        };
+        let before = server.requests().len();
+        let res = s.download(&param, std::io::Cursor::new(vec![])).await;
+        assert!(matches!(res, Err(ref e) if !e.is_retryable()));
+        assert_eq!(server.requests().len(), before + 1);
+
+        // retries give up eventually
+        let param = DownloadParam {
+            info: DurlInfo::new(BODY.len() as u64, format!("http://{}/primary.m4s", server.host())),
+            retry: Some(
+                RetryPolicyBuilder::default()
+                    .max_retries(2)
+                    .base_delay(std::time::Duration::from_millis(1))
+                    .build()?,
+            ),
+            ..param
+        };
+        let before = server.requests().len();
+        assert!(s.download(&param, std::io::Cursor::new(vec![])).await.is_err());
+        assert_eq!(server.requests().len(), before + 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_validate() -> anyhow::Result<()> {
+        use bili_mock::MockResponse;
+        const BODY: &[u8] = b"0123456789abcdefghij";
+        let range = serve_range(BODY);
+        let server = bili_mock::MockServer::start(move |req| match req.path.as_str() {
+            "/forbidden.m4s" => MockResponse {
+                status: 403,
+                headers: vec![("Content-Type".to_owned(), "text/html".to_owned())],
+                body: b"<html>403 Forbidden</html>".to_vec(),
+            },
+            "/full.m4s" => MockResponse {
+                status: 200,
+                headers: vec![],
+                body: BODY.to_vec(),
+            },
+            "/shifted.m4s" => {
+                let mut resp = range(req);
+                resp.headers = vec![("Content-Range".to_owned(), "bytes 1-10/20".to_owned())];
+                resp
+            }
+            _ => range(req),
+        })
+        .await;
+        let s = Service::new();
+        let download = |path: &str, chunk_size: u64| {
+            let param = DownloadParam {
+                info: DurlInfo::new(BODY.len() as u64, format!("http://{}{}", server.host(), path)),
+                chunk_size: Some(chunk_size),
+                conn_pool: Some(1),
+                journal: None,
+                retry: Some(RetryPolicy::none()),
+                progress: None,
+            };
+            let s = &s;
+            async move {
+                let mut out = std::io::Cursor::new(vec![]);
+                s.download(&param, &mut out).await.map(|_| out.into_inner())
+            }
+        };
+
+        assert_eq!(download("/ok.m4s", 10).await?, BODY);
+        assert!(matches!(
+            download("/forbidden.m4s", 10).await,
+            Err(Error::HttpStatus(403))
+        ));
+        assert!(matches!(
+            download("/full.m4s", 10).await,
+            Err(Error::RangeIgnored(0, 9))
+        ));
+        // a single chunk asks the whole file, 200 is fine
+        assert_eq!(download("/full.m4s", 20).await?, BODY);
+        assert!(matches!(
+            download("/shifted.m4s", 10).await,
+            Err(Error::ContentRangeMismatch(0, 9, 20, Some(_)))
+        ));
+
+        let track = |path: &str| {
+            serde_json::from_str::<DashStream>(&format!(
+                r#"{{"id":80,"codecid":7,"codecs":"avc1","bandwidth":1,"base_url":"http://{}{}","backup_url":null,"mime_type":"video/mp4","segment_base":{{"initialization":"0-1","index_range":"2-3"}}}}"#,
+                server.host(),
+                path
+            ))
+        };
+        assert_eq!(*s.get_track_info(&track("/ok.m4s")?).await?.size(), 20);
+        assert!(matches!(
+            s.get_track_info(&track("/forbidden.m4s")?).await,
+            Err(Error::HttpStatus(403))
+        ));
+        assert!(matches!(
+            s.get_track_info(&track("/full.m4s")?).await,
+            Err(Error::RangeIgnored(0, 0))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_rate_limit() -> anyhow::Result<()> {
+        let body = vec![7u8; 200_000];
+        let server = bili_mock::MockServer::start(serve_range(body.clone())).await;
+        // 200KB at 1MB/s, 4 connections share the limit
+        let s = Service::builder()
+            .rate_limiter(RateLimiter::new(Some(1_000_000)))
+            .build()?;
+        let param = DownloadParam {
+            info: DurlInfo::new(body.len() as u64, format!("http://{}/a.m4s", server.host())),
+            chunk_size: Some(50_000),
+            conn_pool: Some(4),
+            journal: None,
+            retry: None,
+            progress: None,
+        };
+        // only a lower bound, a loaded machine is slow anyway
+        let start = std::time::Instant::now();
+        let mut out = std::io::Cursor::new(vec![]);
+        s.download(&param, &mut out).await?;
+        assert_eq!(out.into_inner(), body);
+        assert!(start.elapsed() >= std::time::Duration::from_millis(150));
+
+        s.rate_limiter().set_rate(None);
+        let mut out = std::io::Cursor::new(vec![]);
+        s.download(&param, &mut out).await?;
+        assert_eq!(out.into_inner(), body);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_download_read_timeout() -> anyhow::Result<()> {
+        const BODY: &[u8] = b"0123456789abcdefghij";
+        let range = serve_range(BODY);
+        let server = bili_mock::MockServer::start(move |req| {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            range(req)
+        })
+        .await;
+        let s = Service::builder()
+            .read_timeout(std::time::Duration::from_millis(50))
+            .build()?;
+        let param = DownloadParam {
+            info: DurlInfo::new(BODY.len() as u64, format!("http://{}/a.m4s", server.host())),
+            chunk_size: None,
+            conn_pool: None,
+            journal: None,
+            retry: Some(RetryPolicy::none()),
+            progress: None,
+        };
+        match s.download(&param, std::io::Cursor::new(vec![])).await {
+            Err(e @ Error::IOError(_)) => assert!(e.is_retryable()),
+            res => panic!("unexpected {:?}", res),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicyBuilder::default()
+            .base_delay(std::time::Duration::from_millis(100))
+            .max_delay(std::time::Duration::from_millis(350))
+            .jitter(false)
+            .build()
+            .unwrap();
+        let delays = (0..4).map(|i| policy.delay(i).as_millis()).collect::<Vec<_>>();
+        assert_eq!(delays, vec![100, 200, 350, 350]);
+        let jitter = RetryPolicy::default().delay(1);
+        assert!(jitter >= std::time::Duration::from_millis(500));
+        assert!(jitter <= std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_get_basic_info() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let id = VideoId::BVID("BV1qJ4m1Y71G".to_owned());
+        let basic_info = s.get_basic_info(&id).await?;
+        assert!(*basic_info.cid() > 0);
+
+        let download_info = s
+            .get_download_info(&GetDownloadInfoParam {
+                id: id.clone(),
+                cid: *basic_info.cid(),
+                clarity: Clarity::Low,
+            })
+            .await?;
+        assert_eq!(*download_info.size(), 4096);
+        assert_eq!(download_info.urls().len(), 2);
+
+        let dash = s
+            .get_dash_info(&GetDownloadInfoParam {
+                id,
+                cid: *basic_info.cid(),
+                clarity: Clarity::Default,
+            })
             .await?;
-        // tokio::fs::create_dir_all("tests_download").await?;
-        // let mut file =
-        //     tokio::fs::File::create(format!("tests_download/{}.mp4", basic_info.title())).await?;
-        // let download_info = s
-        //     .get_download_info(&GetDownloadInfoParam {
-        //         id: VideoId::BVID("BV1qJ4m1Y71G".to_owned()),
-        //         cid: *basic_info.cid(),
-        //         clarity: Clarity::Low,
-        //     })
-        //     .await?;
-        // s.download(
-        //     &DownloadParam {
-        //         info: download_info,
-        //         chunk_size: Some(4 * 1024 * 1024),
-        //         conn_pool: Some(8),
-        //     },
-        //     &mut file,
-        // )
-        // .await?;
+        let audio = dash
+            .select_audio(&DashSelector::default())
+            .ok_or(anyhow::anyhow!("no audio"))?;
+        let body = (0..10_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        server.media("BV1qJ4m1Y71G-30280.m4s", body.clone());
+        let track = s.get_track_info(audio).await?;
+        assert_eq!(*track.size(), body.len() as u64);
+
+        let mut out = std::io::Cursor::new(vec![]);
+        let param = DownloadParam {
+            info: track,
+            chunk_size: Some(4096),
+            conn_pool: Some(2),
+            journal: None,
+            retry: None,
+            progress: None,
+        };
+        s.download(&param, &mut out).await?;
+        assert_eq!(out.into_inner(), body);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_pages() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let id = VideoId::BVID("BV1Rs4y1c7Mp".to_owned());
+        let pages = s.get_pages(&id).await?;
+        assert_eq!(
+            pages.iter().map(|p| *p.page()).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(pages[1].part(), "第二课 所有权");
+        assert_eq!(*pages[1].duration(), 1480);
+        assert_eq!(pages[2].dimension().map(|d| *d.rotate()), Some(1));
+        // basic info is still the first page
+        let basic_info = s.get_basic_info(&id).await?;
+        assert_eq!(*basic_info.cid(), *pages[0].cid());
+        assert_eq!(basic_info.title(), pages[0].part());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_video_detail() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let detail = s
+            .get_video_detail(&VideoId::BVID("BV1qJ4m1Y71G".to_owned()))
+            .await?;
+        assert_eq!(*detail.aid(), 1652783491);
+        assert!(detail.description().contains("第二行"));
+        assert!(detail.is_original());
+        assert_eq!(*detail.stat().like(), 90211);
+        assert_eq!(*detail.stat().his_rank(), 38);
+        assert!(*detail.rights().download() && *detail.rights().is_cooperation());
+        assert!(!*detail.rights().ugc_pay());
+        assert_eq!(*detail.dimension().width(), 1280);
+        assert_eq!(
+            detail.staff().iter().map(|s| s.title().as_str()).collect::<Vec<_>>(),
+            vec!["UP主", "剪辑"]
+        );
+        assert_eq!(detail.honors()[0].desc(), "全站排行榜最高第38名");
+        assert_eq!(
+            detail.tags().iter().map(|t| t.tag_name().as_str()).collect::<Vec<_>>(),
+            vec!["数码", "测评"]
+        );
+        assert_eq!(detail.pages().len(), 1);
+        assert_eq!(*detail.season_id(), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_video_detail_round_trip() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let detail = s
+            .get_video_detail(&VideoId::BVID("BV1qJ4m1Y71G".to_owned()))
+            .await?;
+        let json = serde_json::to_value(&detail)?;
+        let back = serde_json::from_value::<VideoDetail>(json.clone())?;
+        assert_eq!(serde_json::to_value(&back)?, json);
+        assert_eq!(back.description(), detail.description());
+        assert_eq!(back.owner().uid(), detail.owner().uid());
+        assert_eq!(back.honors()[0].desc(), "全站排行榜最高第38名");
+        assert!(*back.rights().download() && !*back.rights().ugc_pay());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unknown_video() -> anyhow::Result<()> {
+        let server = bili_mock::FakeBili::start().await;
+        let s = Service::builder()
+            .protocol(Protocol::HTTP)
+            .api_host(server.host())
+            .build()?;
+        let res = s.get_basic_info(&VideoId::BVID("BV1xx411c7mD".to_owned())).await;
+        assert!(matches!(res, Err(Error::APIErr(-404, _))));
         Ok(())
     }
 }
@@ -0,0 +1,129 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::*;
+
+/// sidecar of a chunked download, records which chunks are already on disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Journal {
+    size: u64,
+    chunk_size: u64,
+    /// start offsets of finished chunks
+    done: BTreeSet<u64>,
+}
+
+impl Journal {
+    pub fn new(size: u64, chunk_size: u64) -> Self {
+        Self {
+            size,
+            chunk_size: chunk_size.max(1),
+            done: BTreeSet::new(),
+        }
+    }
+
+    /// `{target}.part.json`
+    pub fn path_for(target: impl AsRef<Path>) -> PathBuf {
+        let mut path = target.as_ref().as_os_str().to_owned();
+        path.push(".part.json");
+        PathBuf::from(path)
+    }
+
+    /// resume from `path`, a missing, broken or mismatched journal starts over
+    pub async fn load(path: impl AsRef<Path>, size: u64, chunk_size: u64) -> Self {
+        let fresh = Self::new(size, chunk_size);
+        match tokio::fs::read(path).await {
+            Ok(content) => match serde_json::from_slice::<Self>(&content) {
+                Ok(journal)
+                    if journal.size == fresh.size && journal.chunk_size == fresh.chunk_size =>
+                {
+                    journal
+                }
+                _ => fresh,
+            },
+            Err(_) => fresh,
+        }
+    }
+
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_vec(self).map_err(|e| Error::Unknown(e.to_string()))?;
+        // write then rename, a crash never leaves a half written journal
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, content).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+
+    /// true if no chunk is finished, the target should be truncated
+    pub fn is_fresh(&self) -> bool {
+        self.done.is_empty()
+    }
+
+    /// every chunk as an inclusive byte range
+    pub fn chunks(&self) -> impl Iterator<Item = std::ops::RangeInclusive<u64>> + '_ {
+        (0..self.size)
+            .step_by(self.chunk_size as usize)
+            .map(|start| start..=(start + self.chunk_size - 1).min(self.size - 1))
+    }
+
+    /// chunks not on disk yet
+    pub fn pending(&self) -> Vec<std::ops::RangeInclusive<u64>> {
+        self.chunks()
+            .filter(|r| !self.done.contains(r.start()))
+            .collect()
+    }
+
+    /// bytes already on disk
+    pub fn finished(&self) -> u64 {
+        self.chunks()
+            .filter(|r| self.done.contains(r.start()))
+            .map(|r| r.end() - r.start() + 1)
+            .sum()
+    }
+
+    pub fn mark(&mut self, chunk: &std::ops::RangeInclusive<u64>) {
+        self.done.insert(*chunk.start());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        let mut journal = Journal::new(10, 4);
+        assert_eq!(journal.pending(), vec![0..=3, 4..=7, 8..=9]);
+        assert!(journal.is_fresh());
+        journal.mark(&(4..=7));
+        assert_eq!(journal.pending(), vec![0..=3, 8..=9]);
+        assert_eq!(journal.finished(), 4);
+        journal.mark(&(8..=9));
+        assert_eq!(journal.finished(), 6);
+        assert!(Journal::new(0, 4).pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_load_and_save() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("bili-journal-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = Journal::path_for(dir.join("a.0.m4s"));
+        assert_eq!(path, dir.join("a.0.m4s.part.json"));
+
+        assert!(Journal::load(&path, 10, 4).await.is_fresh());
+        let mut journal = Journal::new(10, 4);
+        journal.mark(&(0..=3));
+        journal.save(&path).await?;
+        assert_eq!(Journal::load(&path, 10, 4).await, journal);
+        // another track or chunk size never resumes
+        assert!(Journal::load(&path, 11, 4).await.is_fresh());
+        assert!(Journal::load(&path, 10, 5).await.is_fresh());
+        tokio::fs::write(&path, "{").await?;
+        assert!(Journal::load(&path, 10, 4).await.is_fresh());
+
+        tokio::fs::remove_dir_all(dir).await?;
+        Ok(())
+    }
+}
@@ -4,12 +4,24 @@
 pub mod consts;
 pub mod prelude;
 
+mod credential;
+mod danmaku;
 mod error;
 mod impls;
+mod journal;
 mod models;
+mod subtitle;
+mod wbi;
 
+pub use credential::*;
+pub use danmaku::*;
 pub use error::*;
+pub use journal::Journal;
 pub use models::*;
+pub use network_tools;
+pub use network_tools::{ProxyConfig, RateLimiter, RetryPolicy, RetryPolicyBuilder};
+pub use subtitle::*;
+pub use wbi::WbiKeys;
 
 pub use impls::*;
 
@@ -2,12 +2,21 @@ use std::collections::HashMap;
 
 use derive_builder::Builder;
 use derive_getters::Getters;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use super::credential::Credential;
 
 #[derive(Debug, Clone, Deserialize, Getters, Builder)]
 pub struct SeasonList {
     season_id: u64,
     season_name: String,
+    /// introduction of the season, may be empty
+    #[builder(default)]
+    #[serde(default)]
+    intro: String,
+    #[builder(default)]
+    #[serde(default)]
+    cover_url: String,
     owner: Owner,
     sections: Vec<BasicView>,
 }
@@ -33,6 +42,209 @@ pub struct View {
     season_id: Option<u64>,
 }
 
+/// everything `/x/web-interface/view` knows about a video, plus its tags
+///
+/// serialized under the api names, so it reads back
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct VideoDetail {
+    aid: u64,
+    bvid: String,
+    /// cid of the first page
+    cid: u64,
+    title: String,
+    #[serde(rename = "desc")]
+    description: String,
+    #[serde(rename = "pic")]
+    pic_url: String,
+    /// id of the sub zone
+    tid: u32,
+    /// name of the sub zone
+    tname: String,
+    /// 1: original, 2: reposted
+    copyright: u8,
+    /// unix seconds of publishing
+    pubdate: i64,
+    /// unix seconds of uploading
+    ctime: i64,
+    /// seconds of all pages
+    duration: u64,
+    /// count of pages
+    videos: u32,
+    owner: Owner,
+    stat: VideoStat,
+    dimension: Dimension,
+    rights: VideoRights,
+    /// co-creators of a joint upload, empty otherwise
+    #[serde(default)]
+    staff: Vec<Staff>,
+    #[serde(
+        default,
+        rename = "honor_reply",
+        serialize_with = "honor_reply",
+        deserialize_with = "honors"
+    )]
+    honors: Vec<Honor>,
+    #[serde(default)]
+    pages: Vec<VideoPage>,
+    is_season_display: Option<bool>,
+    season_id: Option<u64>,
+    /// not part of the view itself
+    #[serde(default)]
+    tags: Vec<Tag>,
+}
+
+impl VideoDetail {
+    pub fn is_original(&self) -> bool {
+        self.copyright == 1
+    }
+
+    pub(crate) fn with_tags(mut self, tags: Vec<Tag>) -> Self {
+        self.tags = tags;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Getters)]
+pub struct VideoStat {
+    view: u64,
+    danmaku: u64,
+    reply: u64,
+    favorite: u64,
+    coin: u64,
+    share: u64,
+    like: u64,
+    /// best rank in the all-site ranking, 0 if never ranked
+    his_rank: u32,
+}
+
+/// permission flags, `0`/`1` in the api
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Getters)]
+#[serde(default)]
+pub struct VideoRights {
+    #[serde(deserialize_with = "int_bool")]
+    download: bool,
+    #[serde(deserialize_with = "int_bool")]
+    no_reprint: bool,
+    #[serde(deserialize_with = "int_bool")]
+    is_cooperation: bool,
+    /// paid video
+    #[serde(deserialize_with = "int_bool")]
+    ugc_pay: bool,
+    /// interactive video
+    #[serde(deserialize_with = "int_bool")]
+    is_stein_gate: bool,
+    #[serde(deserialize_with = "int_bool")]
+    is_360: bool,
+    #[serde(deserialize_with = "int_bool")]
+    hd5: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct Staff {
+    mid: u64,
+    /// role like `UP主` or `剪辑`
+    title: String,
+    name: String,
+    #[serde(rename = "face")]
+    face_url: String,
+    #[serde(default)]
+    follower: u64,
+}
+
+/// badge like `全站排行榜最高第3名` or `入站必刷收录`
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct Honor {
+    /// category of the badge
+    #[serde(rename = "type")]
+    kind: u32,
+    desc: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct Tag {
+    tag_id: u64,
+    tag_name: String,
+}
+
+/// `{"honor": [..]}` or `{}`
+fn honors<'de, D>(deserializer: D) -> std::result::Result<Vec<Honor>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct HonorReply {
+        #[serde(default)]
+        honor: Option<Vec<Honor>>,
+    }
+    Ok(Option::<HonorReply>::deserialize(deserializer)?
+        .and_then(|r| r.honor)
+        .unwrap_or_default())
+}
+
+/// back to `{"honor": [..]}`
+fn honor_reply<S>(honors: &[Honor], serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    #[derive(Serialize)]
+    struct HonorReply<'a> {
+        honor: &'a [Honor],
+    }
+    HonorReply { honor: honors }.serialize(serializer)
+}
+
+/// `0`/`1` from the api, `false`/`true` once serialized
+fn int_bool<'de, D>(deserializer: D) -> std::result::Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum IntBool {
+        Int(i64),
+        Bool(bool),
+    }
+    Ok(match IntBool::deserialize(deserializer)? {
+        IntBool::Int(v) => v != 0,
+        IntBool::Bool(v) => v,
+    })
+}
+
+#[derive(Debug, Clone, Deserialize, Getters)]
+pub struct NavInfo {
+    #[serde(rename = "isLogin")]
+    is_login: bool,
+    mid: Option<u64>,
+    uname: Option<String>,
+    #[serde(rename = "vipStatus")]
+    vip_status: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Getters)]
+pub struct QrCode {
+    /// content of the qrcode, open it with the bilibili app
+    url: String,
+    qrcode_key: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Getters)]
+pub struct CookieInfo {
+    /// cookies should be refreshed
+    refresh: bool,
+    /// server time in millis
+    timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QrLoginState {
+    /// not scanned yet
+    Waiting,
+    /// scanned, waiting for confirm in the app
+    Scanned,
+    Confirmed(Credential),
+    Expired,
+}
+
 #[derive(Debug, Clone)]
 pub enum VideoId {
     AID(u64),
@@ -48,7 +260,7 @@ impl std::fmt::Display for VideoId {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Getters)]
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
 pub struct Owner {
     #[serde(rename = "mid")]
     uid: u64,
@@ -65,6 +277,37 @@ pub struct VideoMetadata {
     title: String,
 }
 
+/// a part of a multi-part (multi-P) video
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct VideoPage {
+    cid: u64,
+    /// 1 based
+    page: u32,
+    /// title of the part
+    part: String,
+    /// seconds
+    duration: u64,
+    #[serde(default)]
+    dimension: Option<Dimension>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Getters)]
+pub struct Dimension {
+    width: u32,
+    height: u32,
+    /// 1 if width and height are swapped
+    rotate: u32,
+}
+
+impl From<VideoPage> for VideoMetadata {
+    fn from(page: VideoPage) -> Self {
+        Self {
+            cid: page.cid,
+            title: page.part,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Getters)]
 pub struct DownloadInfo {
     // accept_quality: Vec<u32>,
@@ -75,13 +318,726 @@ pub struct DownloadInfo {
 pub struct DurlInfo {
     size: u64,
     url: String,
+    /// mirrors of `url`, tried in order when it keeps failing
+    #[serde(default)]
+    backup_url: Option<Vec<String>>,
 }
 
-#[derive(Debug, Getters)]
+impl DurlInfo {
+    pub fn new(size: u64, url: String) -> Self {
+        Self {
+            size,
+            url,
+            backup_url: None,
+        }
+    }
+
+    pub fn with_backup_url(mut self, backup_url: Vec<String>) -> Self {
+        self.backup_url = Some(backup_url);
+        self
+    }
+
+    /// `url` followed by the backups
+    pub fn urls(&self) -> Vec<String> {
+        std::iter::once(self.url.clone())
+            .chain(self.backup_url.iter().flatten().cloned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Getters)]
+pub struct DashInfo {
+    duration: u64,
+    video: Vec<DashStream>,
+    // null when the video has no sound
+    #[serde(default)]
+    audio: Option<Vec<DashStream>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Getters)]
+pub struct DashStream {
+    /// quality id, `qn` for video and 30216/30232/30280 for audio
+    id: u32,
+    /// 7: avc, 12: hevc, 13: av1, 0 for audio
+    codecid: u32,
+    codecs: String,
+    bandwidth: u64,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    #[serde(default)]
+    frame_rate: String,
+    #[serde(alias = "baseUrl")]
+    base_url: String,
+    #[serde(alias = "backupUrl", default)]
+    backup_url: Option<Vec<String>>,
+    #[serde(alias = "mimeType", default)]
+    mime_type: String,
+    #[serde(alias = "SegmentBase")]
+    segment_base: Option<SegmentBase>,
+}
+
+#[derive(Debug, Clone, Deserialize, Getters)]
+pub struct SegmentBase {
+    #[serde(alias = "Initialization")]
+    initialization: String,
+    #[serde(alias = "indexRange")]
+    index_range: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    AVC,
+    HEVC,
+    AV1,
+}
+
+impl VideoCodec {
+    pub fn codecid(&self) -> u32 {
+        match self {
+            VideoCodec::AVC => 7,
+            VideoCodec::HEVC => 12,
+            VideoCodec::AV1 => 13,
+        }
+    }
+}
+
+/// pick dash tracks, highest quality under the limits wins
+#[derive(Debug, Clone, Default, Builder)]
+#[builder(default)]
+pub struct DashSelector {
+    /// max video quality id (`qn`), e.g. 80 for 1080P
+    max_quality: Option<u32>,
+    /// preferred codecs in order, any codec when empty
+    codecs: Vec<VideoCodec>,
+    /// max audio quality id
+    max_audio_quality: Option<u32>,
+}
+
+impl DashInfo {
+    pub fn select_video(&self, selector: &DashSelector) -> Option<&DashStream> {
+        // earlier codec in `codecs` ranks higher
+        let codec_rank = |v: &DashStream| {
+            selector
+                .codecs
+                .iter()
+                .position(|c| c.codecid() == v.codecid)
+                .map(|i| selector.codecs.len() - i)
+        };
+        self.video
+            .iter()
+            .filter(|v| selector.max_quality.is_none_or(|max| v.id <= max))
+            .filter(|v| selector.codecs.is_empty() || codec_rank(v).is_some())
+            .max_by_key(|v| (v.id, codec_rank(v), v.bandwidth))
+    }
+
+    pub fn select_audio(&self, selector: &DashSelector) -> Option<&DashStream> {
+        self.audio
+            .iter()
+            .flatten()
+            .filter(|a| selector.max_audio_quality.is_none_or(|max| a.id <= max))
+            .max_by_key(|a| (a.bandwidth, a.id))
+    }
+}
+
+#[derive(Debug, Clone, Getters)]
 pub struct DownloadParam {
     pub info: DurlInfo,
     pub chunk_size: Option<u64>,
     pub conn_pool: Option<u8>,
+    /// `.part.json` journal path, finished chunks are skipped when resuming
+    pub journal: Option<std::path::PathBuf>,
+    /// `RetryPolicy::default()` if not set
+    pub retry: Option<network_tools::RetryPolicy>,
+    /// receives `DownloadEvent`s, dropped receivers are ignored
+    pub progress: Option<tokio::sync::mpsc::UnboundedSender<DownloadEvent>>,
+}
+
+/// progress of `VideoService::download`, byte counts sum up to `Started::size`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadEvent {
+    /// `finished` bytes are already on disk from a journal
+    Started { size: u64, finished: u64 },
+    ChunkStarted { range: std::ops::RangeInclusive<u64> },
+    /// bytes received for a chunk
+    Bytes(u64),
+    /// bytes received before the failure are kept, the retry asks for the rest
+    ChunkRetried {
+        range: std::ops::RangeInclusive<u64>,
+        attempt: u32,
+        error: String,
+    },
+    /// chunk written to the writer
+    ChunkFinished { range: std::ops::RangeInclusive<u64> },
+    Finished,
+}
+
+/// a CC or AI subtitle of a page, fetch it with `SubtitleService::get_subtitle`
+#[derive(Debug, Clone, Getters)]
+pub struct SubtitleTrack {
+    id: u64,
+    /// `zh-CN`, `en-US`, AI subtitles are prefixed like `ai-zh`
+    lang: String,
+    /// human readable, e.g. `中文（中国）`
+    lang_doc: String,
+    /// url of the BCC json, empty when the subtitle needs login
+    url: String,
+    is_ai: bool,
+}
+
+impl SubtitleTrack {
+    pub fn new(id: u64, lang: String, lang_doc: String, url: String, is_ai: bool) -> Self {
+        Self {
+            id,
+            lang,
+            lang_doc,
+            url,
+            is_ai,
+        }
+    }
+}
+
+/// format of an image, bilibili's image cdn converts on the fly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpg,
+    Png,
+    Webp,
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Avif => "avif",
+        }
+    }
+
+    /// detect from magic bytes
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        match data {
+            [0xff, 0xd8, 0xff, ..] => Some(ImageFormat::Jpg),
+            [0x89, b'P', b'N', b'G', ..] => Some(ImageFormat::Png),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => {
+                Some(ImageFormat::Webp)
+            }
+            [_, _, _, _, b'f', b't', b'y', b'p', b'a', b'v', b'i', b'f', ..] => {
+                Some(ImageFormat::Avif)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// resize and convert a cover or avatar, the original when nothing is set
+#[derive(Debug, Clone, Default, Builder, Getters)]
+#[builder(default)]
+pub struct ImageParam {
+    /// `{w}w`, height follows the ratio when only width is set
+    #[builder(setter(strip_option))]
+    width: Option<u32>,
+    #[builder(setter(strip_option))]
+    height: Option<u32>,
+    #[builder(setter(strip_option))]
+    format: Option<ImageFormat>,
+}
+
+impl ImageParam {
+    /// `url` with the `@{w}w_{h}h_1c.{format}` suffix of the image cdn
+    pub fn url(&self, url: &str) -> String {
+        // drop the suffix a url may already carry
+        let url = match url.rsplit_once('/') {
+            Some((dir, name)) => match name.split_once('@') {
+                Some((name, _)) => format!("{}/{}", dir, name),
+                None => url.to_owned(),
+            },
+            None => url.to_owned(),
+        };
+        let mut size = vec![];
+        if let Some(w) = self.width {
+            size.push(format!("{}w", w));
+        }
+        if let Some(h) = self.height {
+            size.push(format!("{}h", h));
+        }
+        if self.width.is_some() && self.height.is_some() {
+            // crop to fit instead of stretching
+            size.push("1c".to_owned());
+        }
+        let format = self
+            .format
+            .map(|f| format!(".{}", f.extension()))
+            .unwrap_or_default();
+        if size.is_empty() && format.is_empty() {
+            return url;
+        }
+        format!("{}@{}{}", url, size.join("_"), format)
+    }
+}
+
+/// image bytes from `ImageService::get_image`
+#[derive(Debug, Clone, Getters)]
+pub struct Image {
+    data: Vec<u8>,
+    /// `None` for formats not in `ImageFormat`
+    format: Option<ImageFormat>,
+}
+
+impl Image {
+    pub fn new(data: Vec<u8>) -> Self {
+        let format = ImageFormat::sniff(&data);
+        Self { data, format }
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// what `SearchService::search` looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchType {
+    Video,
+    User,
+    Bangumi,
+    LiveRoom,
+    Article,
+}
+
+impl SearchType {
+    /// `search_type` of the api
+    pub fn param(&self) -> &'static str {
+        match self {
+            SearchType::Video => "video",
+            SearchType::User => "bili_user",
+            SearchType::Bangumi => "media_bangumi",
+            SearchType::LiveRoom => "live_room",
+            SearchType::Article => "article",
+        }
+    }
+}
+
+/// `Fans` and `Level` sort users, the others videos and articles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOrder {
+    TotalRank,
+    Click,
+    PubDate,
+    Danmaku,
+    Favorite,
+    Comment,
+    Fans,
+    Level,
+}
+
+impl SearchOrder {
+    pub fn param(&self) -> &'static str {
+        match self {
+            SearchOrder::TotalRank => "totalrank",
+            SearchOrder::Click => "click",
+            SearchOrder::PubDate => "pubdate",
+            SearchOrder::Danmaku => "dm",
+            SearchOrder::Favorite => "stow",
+            SearchOrder::Comment => "scores",
+            SearchOrder::Fans => "fans",
+            SearchOrder::Level => "level",
+        }
+    }
+}
+
+/// length of searched videos
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchDuration {
+    Under10Min,
+    From10To30Min,
+    From30To60Min,
+    Over60Min,
+}
+
+impl SearchDuration {
+    pub fn param(&self) -> u8 {
+        match self {
+            SearchDuration::Under10Min => 1,
+            SearchDuration::From10To30Min => 2,
+            SearchDuration::From30To60Min => 3,
+            SearchDuration::Over60Min => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct SearchParam {
+    #[builder(setter(into))]
+    keyword: String,
+    #[builder(default = "SearchType::Video")]
+    search_type: SearchType,
+    /// relevance when not set
+    #[builder(default, setter(strip_option))]
+    order: Option<SearchOrder>,
+    #[builder(default, setter(strip_option))]
+    duration: Option<SearchDuration>,
+    /// partition (`tid`) of videos, every partition when not set
+    #[builder(default, setter(strip_option))]
+    tid: Option<u32>,
+    /// 1 based
+    #[builder(default = "1")]
+    page: u32,
+}
+
+impl SearchParam {
+    pub fn with_page(mut self, page: u32) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub(crate) fn get_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = vec![
+            ("keyword", self.keyword.clone()),
+            ("search_type", self.search_type.param().to_owned()),
+            ("page", self.page.to_string()),
+        ];
+        if let Some(order) = self.order {
+            query.push(("order", order.param().to_owned()));
+        }
+        if let Some(duration) = self.duration {
+            query.push(("duration", duration.param().to_string()));
+        }
+        if let Some(tid) = self.tid {
+            query.push(("tids", tid.to_string()));
+        }
+        query
+    }
+}
+
+/// a page of search results, all of the `SearchType` searched
+#[derive(Debug, Clone, Getters)]
+pub struct SearchPage {
+    /// 1 based
+    page: u32,
+    page_size: u32,
+    num_results: u64,
+    num_pages: u32,
+    results: Vec<SearchResult>,
+}
+
+impl SearchPage {
+    pub(crate) fn new(
+        page: u32,
+        page_size: u32,
+        num_results: u64,
+        num_pages: u32,
+        results: Vec<SearchResult>,
+    ) -> Self {
+        Self {
+            page,
+            page_size,
+            num_results,
+            num_pages,
+            results,
+        }
+    }
+
+    pub fn into_results(self) -> Vec<SearchResult> {
+        self.results
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum SearchResult {
+    Video(SearchVideo),
+    User(SearchUser),
+    Bangumi(SearchBangumi),
+    LiveRoom(SearchLiveRoom),
+    Article(SearchArticle),
+}
+
+/// titles are plain text, the `<em>` around the keyword removed
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct SearchVideo {
+    aid: u64,
+    bvid: String,
+    #[serde(deserialize_with = "plain_text")]
+    title: String,
+    /// name of the uploader
+    author: String,
+    mid: u64,
+    #[serde(default)]
+    description: String,
+    #[serde(rename(deserialize = "pic"), deserialize_with = "https_url")]
+    pic_url: String,
+    /// view count
+    #[serde(default)]
+    play: u64,
+    #[serde(default)]
+    danmaku: u64,
+    #[serde(default)]
+    favorites: u64,
+    /// seconds
+    #[serde(deserialize_with = "clock_secs")]
+    duration: u64,
+    pubdate: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct SearchUser {
+    mid: u64,
+    #[serde(rename(deserialize = "uname"), deserialize_with = "plain_text")]
+    name: String,
+    #[serde(default, rename(deserialize = "usign"))]
+    sign: String,
+    #[serde(default)]
+    fans: u64,
+    #[serde(default)]
+    videos: u64,
+    #[serde(rename(deserialize = "upic"), deserialize_with = "https_url")]
+    face_url: String,
+    #[serde(default)]
+    level: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct SearchBangumi {
+    media_id: u64,
+    season_id: u64,
+    #[serde(deserialize_with = "plain_text")]
+    title: String,
+    #[serde(rename(deserialize = "cover"), deserialize_with = "https_url")]
+    cover_url: String,
+    #[serde(default, rename(deserialize = "desc"))]
+    description: String,
+    /// episodes, 0 when unknown
+    #[serde(default)]
+    ep_size: u32,
+    pubtime: i64,
+    /// out of 10, `None` before enough ratings
+    #[serde(default, rename(deserialize = "media_score"), deserialize_with = "media_score")]
+    score: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct SearchLiveRoom {
+    #[serde(rename(deserialize = "roomid"))]
+    room_id: u64,
+    uid: u64,
+    uname: String,
+    #[serde(deserialize_with = "plain_text")]
+    title: String,
+    /// watching now
+    #[serde(default)]
+    online: u64,
+    #[serde(rename(deserialize = "live_status"), deserialize_with = "int_bool")]
+    is_live: bool,
+    #[serde(rename(deserialize = "user_cover"), deserialize_with = "https_url")]
+    cover_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct SearchArticle {
+    /// `cv` id
+    id: u64,
+    mid: u64,
+    #[serde(deserialize_with = "plain_text")]
+    title: String,
+    #[serde(default, rename(deserialize = "desc"))]
+    description: String,
+    #[serde(default)]
+    view: u64,
+    #[serde(default)]
+    like: u64,
+    #[serde(default)]
+    reply: u64,
+    pub_time: i64,
+    #[serde(default)]
+    image_urls: Vec<String>,
+}
+
+/// order of `UserService::get_user_videos`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserVideoOrder {
+    PubDate,
+    Click,
+    Favorite,
+}
+
+impl UserVideoOrder {
+    pub fn param(&self) -> &'static str {
+        match self {
+            UserVideoOrder::PubDate => "pubdate",
+            UserVideoOrder::Click => "click",
+            UserVideoOrder::Favorite => "stow",
+        }
+    }
+}
+
+/// videos submitted by the user `mid`, the `Owner::uid` of their videos
+#[derive(Debug, Clone, Builder, Getters)]
+pub struct UserVideoParam {
+    mid: u64,
+    /// newest first when not set
+    #[builder(default, setter(strip_option))]
+    order: Option<UserVideoOrder>,
+    /// only titles containing it
+    #[builder(default, setter(into, strip_option))]
+    keyword: Option<String>,
+    /// 1 based
+    #[builder(default = "1")]
+    page: u32,
+    /// at most 50
+    #[builder(default = "30")]
+    page_size: u32,
+}
+
+impl UserVideoParam {
+    pub fn with_page(mut self, page: u32) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub(crate) fn get_query(&self) -> Vec<(&'static str, String)> {
+        let order = self.order.unwrap_or(UserVideoOrder::PubDate);
+        let mut query = vec![
+            ("mid", self.mid.to_string()),
+            ("pn", self.page.to_string()),
+            ("ps", self.page_size.to_string()),
+            ("order", order.param().to_owned()),
+        ];
+        if let Some(keyword) = &self.keyword {
+            query.push(("keyword", keyword.clone()));
+        }
+        query
+    }
+}
+
+#[derive(Debug, Clone, Getters)]
+pub struct UserVideoPage {
+    /// 1 based
+    page: u32,
+    page_size: u32,
+    /// videos matching the param, on every page
+    count: u64,
+    videos: Vec<UserVideo>,
+}
+
+impl UserVideoPage {
+    pub(crate) fn new(page: u32, page_size: u32, count: u64, videos: Vec<UserVideo>) -> Self {
+        Self {
+            page,
+            page_size,
+            count,
+            videos,
+        }
+    }
+
+    pub fn into_videos(self) -> Vec<UserVideo> {
+        self.videos
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Getters)]
+pub struct UserVideo {
+    aid: u64,
+    bvid: String,
+    title: String,
+    /// name of the uploader
+    author: String,
+    mid: u64,
+    #[serde(default)]
+    description: String,
+    #[serde(rename(deserialize = "pic"), deserialize_with = "https_url")]
+    pic_url: String,
+    /// view count, 0 when the uploader hides it
+    #[serde(default, deserialize_with = "hidden_count")]
+    play: u64,
+    #[serde(default)]
+    comment: u64,
+    /// seconds
+    #[serde(rename(deserialize = "length"), deserialize_with = "clock_secs")]
+    duration: u64,
+    /// unix seconds it was published
+    created: i64,
+}
+
+/// `<em class="keyword">key</em>word &amp; more` to `keyword & more`
+fn plain_text<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let html = String::deserialize(deserializer)?;
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html.as_str();
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = match rest[start..].find('>') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    text.push_str(rest);
+    Ok(text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&"))
+}
+
+/// search results have scheme-relative `//i0.hdslb.com/..` urls
+fn https_url<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let url = String::deserialize(deserializer)?;
+    if url.starts_with("//") {
+        Ok(format!("https:{}", url))
+    } else {
+        Ok(url)
+    }
+}
+
+/// `MM:SS` or `HH:MM:SS`
+fn clock_secs<'de, D>(deserializer: D) -> std::result::Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let clock = String::deserialize(deserializer)?;
+    clock
+        .split(':')
+        .try_fold(0u64, |secs, part| Some(secs * 60 + part.trim().parse::<u64>().ok()?))
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid duration {}", clock)))
+}
+
+/// counts the uploader hides are `"--"`
+fn hidden_count<'de, D>(deserializer: D) -> std::result::Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Count {
+        Number(u64),
+        Text(String),
+    }
+    Ok(match Count::deserialize(deserializer)? {
+        Count::Number(n) => n,
+        Count::Text(s) => s.parse().unwrap_or(0),
+    })
+}
+
+fn media_score<'de, D>(deserializer: D) -> std::result::Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct MediaScore {
+        score: f64,
+    }
+    Ok(Option::<MediaScore>::deserialize(deserializer)?
+        .map(|s| s.score)
+        .filter(|s| *s > 0.0))
 }
 
 #[derive(Debug)]
@@ -93,9 +1049,26 @@ pub struct GetDownloadInfoParam {
 
 impl GetDownloadInfoParam {
     pub(crate) fn get_query(&self) -> HashMap<&str, String> {
+        self.query_with_fnval(1)
+    }
+
+    pub(crate) fn get_dash_query(&self) -> HashMap<&str, String> {
+        let mut mp = match self.clarity {
+            // 16: dash, 128: 4k
+            Clarity::High => self.query_with_fnval(16 | 128),
+            Clarity::Low | Clarity::Default => self.query_with_fnval(16),
+        };
+        if let Clarity::High = self.clarity {
+            mp.insert("fourk", "1".to_owned());
+        }
+        mp
+    }
+
+    fn query_with_fnval(&self, fnval: u32) -> HashMap<&str, String> {
         let mut mp = HashMap::new();
         // --- deal with static
         mp.insert("fnver", "0".to_owned());
+        mp.insert("fnval", fnval.to_string());
         // --- deal with id
         match self.id {
             VideoId::AID(id) => mp.insert("avid", id.to_string()),
@@ -106,16 +1079,13 @@ impl GetDownloadInfoParam {
         // --- deal with clarity
         match self.clarity {
             Clarity::High => {
-                // TODO login
-                mp.insert("fnval", "1".to_owned());
+                // needs login cookies, see `ServiceBuilder::credential`
                 mp.insert("qn", "112".to_owned());
             }
             Clarity::Low => {
-                mp.insert("fnval", "1".to_owned());
                 mp.insert("qn", "16".to_owned());
             }
             Clarity::Default => {
-                mp.insert("fnval", "1".to_owned());
                 mp.insert("qn", "16".to_owned());
             }
         };
@@ -130,6 +1100,7 @@ pub enum Clarity {
     Default,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum Protocol {
     HTTP,
     HTTPS,
@@ -162,4 +1133,25 @@ where
             code => Err(super::Error::APIErr(code, self.message)),
         }
     }
+
+    /// some api (e.g. nav) still carry data with a non-zero code
+    pub(crate) fn into_data(self) -> Option<T> {
+        self.data
+    }
+
+    /// for apis answering `null` data when there is nothing
+    pub(crate) fn into_optional(self) -> super::Result<Option<T>> {
+        match self.code {
+            0 => Ok(self.data),
+            code => Err(super::Error::APIErr(code, self.message)),
+        }
+    }
+
+    /// for apis without data
+    pub(crate) fn check(self) -> super::Result<()> {
+        match self.code {
+            0 => Ok(()),
+            code => Err(super::Error::APIErr(code, self.message)),
+        }
+    }
 }
@@ -1,22 +1,80 @@
 use tokio::io::{AsyncSeekExt, AsyncWriteExt};
 
+use super::credential::*;
+use super::danmaku::*;
 use super::error::*;
 use super::models::*;
+use super::subtitle::*;
 
-pub trait AcconutService {}
+pub trait AcconutService {
+    /// account of the current cookies, `is_login` is false when anonymous
+    fn get_nav_info(self) -> impl std::future::Future<Output = Result<NavInfo>> + Send;
+
+    fn generate_qrcode(self) -> impl std::future::Future<Output = Result<QrCode>> + Send;
+
+    /// poll about once a second until `Confirmed` or `Expired`
+    fn poll_qrcode(
+        self,
+        qrcode_key: &str,
+    ) -> impl std::future::Future<Output = Result<QrLoginState>> + Send;
+
+    /// whether the login cookies should be refreshed
+    fn get_cookie_info(self) -> impl std::future::Future<Output = Result<CookieInfo>> + Send;
+
+    /// rotate cookies with the refresh_token, the service uses the new ones afterwards
+    ///
+    /// they are saved to `store` before the old refresh_token is confirmed dead,
+    /// so a failed confirm never loses them
+    fn refresh_credential<S>(
+        self,
+        store: &S,
+    ) -> impl std::future::Future<Output = Result<Credential>> + Send
+    where
+        S: CredentialStore + Sync;
+
+    /// refresh when needed and save the new credential, true if refreshed
+    fn refresh_if_needed<S>(
+        self,
+        store: &S,
+    ) -> impl std::future::Future<Output = Result<bool>> + Send
+    where
+        S: CredentialStore + Sync;
+}
 
 pub trait VideoService {
+    /// the first page
     fn get_basic_info(
         self,
         id: &VideoId,
     ) -> impl std::future::Future<Output = Result<VideoMetadata>> + Send;
 
+    /// every page of a video in order, a single one for single-part videos
+    fn get_pages(self, id: &VideoId)
+        -> impl std::future::Future<Output = Result<Vec<VideoPage>>> + Send;
+
+    /// description, stats, rights, staff, honors and tags of a video
+    fn get_video_detail(
+        self,
+        id: &VideoId,
+    ) -> impl std::future::Future<Output = Result<VideoDetail>> + Send;
+
     fn get_download_info(
         self,
         param: &GetDownloadInfoParam,
     ) -> impl std::future::Future<Output = Result<DurlInfo>> + Send;
 
-    fn download<'a, W>(
+    fn get_dash_info(
+        self,
+        param: &GetDownloadInfoParam,
+    ) -> impl std::future::Future<Output = Result<DashInfo>> + Send;
+
+    /// size and url of a dash track, ready for `download`
+    fn get_track_info(
+        self,
+        stream: &DashStream,
+    ) -> impl std::future::Future<Output = Result<DurlInfo>> + Send;
+
+    fn download<W>(
         self,
         param: &DownloadParam,
         writer: W,
@@ -38,8 +96,91 @@ pub trait SeasonService {
     ) -> impl std::future::Future<Output = Result<Option<u64>>> + Send;
 }
 
+pub trait SubtitleService {
+    /// subtitles of a page, CC ones first
+    fn get_subtitle_tracks(
+        self,
+        id: &VideoId,
+        cid: u64,
+    ) -> impl std::future::Future<Output = Result<Vec<SubtitleTrack>>> + Send;
+
+    fn get_subtitle(
+        self,
+        track: &SubtitleTrack,
+    ) -> impl std::future::Future<Output = Result<Subtitle>> + Send;
+}
+
+pub trait DanmakuService {
+    /// the latest danmaku of a page from `list.so`, capped by the video's pool size
+    fn get_danmaku_xml(
+        self,
+        cid: u64,
+    ) -> impl std::future::Future<Output = Result<Vec<Danmaku>>> + Send;
+
+    /// a 6 minutes segment of `seg.so`, `segment_index` is 1 based
+    fn get_danmaku_segment(
+        self,
+        cid: u64,
+        segment_index: u64,
+    ) -> impl std::future::Future<Output = Result<Vec<Danmaku>>> + Send;
+
+    /// every segment of a page lasting `duration` seconds, in time order
+    fn get_danmaku(
+        self,
+        cid: u64,
+        duration: u64,
+    ) -> impl std::future::Future<Output = Result<Vec<Danmaku>>> + Send;
+
+    /// dates (`YYYY-MM-DD`) of `month` (`YYYY-MM`) having a history snapshot, needs login
+    fn get_danmaku_history_dates(
+        self,
+        cid: u64,
+        month: &str,
+    ) -> impl std::future::Future<Output = Result<Vec<String>>> + Send;
+
+    /// danmaku of a page as it was at the end of `date` (`YYYY-MM-DD`), needs login
+    fn get_danmaku_history(
+        self,
+        cid: u64,
+        date: &str,
+    ) -> impl std::future::Future<Output = Result<Vec<Danmaku>>> + Send;
+}
+
+pub trait ImageService {
+    /// a cover or avatar url, e.g. `View::pic_url`, resized and converted by `param`
+    fn get_image(
+        self,
+        url: &str,
+        param: &ImageParam,
+    ) -> impl std::future::Future<Output = Result<Image>> + Send;
+}
+
 pub trait SearchService {
-    fn search_by_keyword<T, O>(key: String, search_type: T, search_opts: Option<O>);
+    /// the `param.page()` page of results
+    fn search(
+        self,
+        param: &SearchParam,
+    ) -> impl std::future::Future<Output = Result<SearchPage>> + Send;
+
+    /// results from `param.page()` to the last page, fetched as consumed
+    fn search_stream(
+        self,
+        param: &SearchParam,
+    ) -> impl futures::Stream<Item = Result<SearchResult>> + Send;
+}
+
+pub trait UserService {
+    /// the `param.page()` page of videos the user submitted
+    fn get_user_videos(
+        self,
+        param: &UserVideoParam,
+    ) -> impl std::future::Future<Output = Result<UserVideoPage>> + Send;
+
+    /// videos from `param.page()` to the last page, fetched as consumed
+    fn user_videos_stream(
+        self,
+        param: &UserVideoParam,
+    ) -> impl futures::Stream<Item = Result<UserVideo>> + Send;
 }
 
 pub trait MusicService {
@@ -0,0 +1,237 @@
+use std::fmt::Write;
+
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+/// a BCC subtitle, the json bilibili serves for CC and AI subtitles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Getters)]
+pub struct Subtitle {
+    body: Vec<SubtitleLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Getters)]
+pub struct SubtitleLine {
+    /// seconds
+    from: f64,
+    to: f64,
+    content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+            SubtitleFormat::Ass => "ass",
+        }
+    }
+}
+
+/// 1080p canvas, white text with a black outline at the bottom center
+const ASS_HEADER: &str = "[Script Info]
+ScriptType: v4.00+
+PlayResX: 1920
+PlayResY: 1080
+WrapStyle: 0
+ScaledBorderAndShadow: yes
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,sans-serif,64,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,3,0,2,40,40,40,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+";
+
+impl Subtitle {
+    pub fn new(body: Vec<SubtitleLine>) -> Self {
+        Self { body }
+    }
+
+    pub fn convert(&self, format: SubtitleFormat) -> String {
+        match format {
+            SubtitleFormat::Srt => self.to_srt(),
+            SubtitleFormat::Vtt => self.to_vtt(),
+            SubtitleFormat::Ass => self.to_ass(),
+        }
+    }
+
+    pub fn to_srt(&self) -> String {
+        let mut out = String::new();
+        for (i, line) in self.lines().enumerate() {
+            let (from, to) = line.millis();
+            let _ = write!(
+                out,
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                clock(from, ','),
+                clock(to, ','),
+                line.text().join("\n")
+            );
+        }
+        out
+    }
+
+    pub fn to_vtt(&self) -> String {
+        let mut out = String::from("WEBVTT\n\n");
+        for line in self.lines() {
+            let (from, to) = line.millis();
+            // cue text is html-like, only these three must be escaped
+            let text = line
+                .text()
+                .join("\n")
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            let _ = write!(
+                out,
+                "{} --> {}\n{}\n\n",
+                clock(from, '.'),
+                clock(to, '.'),
+                text
+            );
+        }
+        out
+    }
+
+    pub fn to_ass(&self) -> String {
+        let mut out = String::from(ASS_HEADER);
+        for line in self.lines() {
+            let (from, to) = line.millis();
+            // `\N` breaks lines
+            let text = line
+                .text()
+                .iter()
+                .map(|t| ass_escape(t))
+                .collect::<Vec<_>>()
+                .join("\\N");
+            let _ = writeln!(
+                out,
+                "Dialogue: 0,{},{},Default,,0,0,0,,{}",
+                ass_clock(from),
+                ass_clock(to),
+                text
+            );
+        }
+        out
+    }
+
+    /// lines with something to show, blank lines would end a cue early
+    fn lines(&self) -> impl Iterator<Item = &SubtitleLine> {
+        self.body.iter().filter(|l| !l.text().is_empty())
+    }
+}
+
+impl SubtitleLine {
+    pub fn new(from: f64, to: f64, content: impl Into<String>) -> Self {
+        Self {
+            from,
+            to,
+            content: content.into(),
+        }
+    }
+
+    fn millis(&self) -> (u64, u64) {
+        let ms = |s: f64| (s.max(0.0) * 1000.0).round() as u64;
+        (ms(self.from), ms(self.to))
+    }
+
+    fn text(&self) -> Vec<&str> {
+        self.content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+}
+
+/// `HH:MM:SS{sep}mmm`
+fn clock(ms: u64, sep: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        sep,
+        ms % 1000
+    )
+}
+
+/// escape override blocks the way ffmpeg does, for a single line of text
+pub(crate) fn ass_escape(line: &str) -> String {
+    line.replace('\\', "\\\\")
+        .replace('{', "\\{")
+        .replace('}', "\\}")
+}
+
+/// `H:MM:SS.cc`
+pub(crate) fn ass_clock(ms: u64) -> String {
+    let cs = (ms + 5) / 10;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        cs / 360_000,
+        cs / 6000 % 60,
+        cs / 100 % 60,
+        cs % 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subtitle() -> Subtitle {
+        Subtitle::new(vec![
+            SubtitleLine::new(0.5, 2.8, "一样的月光"),
+            SubtitleLine::new(3.0, 5.25, "第一行\n\n{二} & <三>"),
+            SubtitleLine::new(6.0, 7.0, " \n "),
+            SubtitleLine::new(3661.125, 3662.0, "完"),
+        ])
+    }
+
+    #[test]
+    fn test_srt() {
+        assert_eq!(
+            subtitle().to_srt(),
+            "1\n00:00:00,500 --> 00:00:02,800\n一样的月光\n\n\
+             2\n00:00:03,000 --> 00:00:05,250\n第一行\n{二} & <三>\n\n\
+             3\n01:01:01,125 --> 01:01:02,000\n完\n\n"
+        );
+    }
+
+    #[test]
+    fn test_vtt() {
+        assert_eq!(
+            subtitle().to_vtt(),
+            "WEBVTT\n\n\
+             00:00:00.500 --> 00:00:02.800\n一样的月光\n\n\
+             00:00:03.000 --> 00:00:05.250\n第一行\n{二} &amp; &lt;三&gt;\n\n\
+             01:01:01.125 --> 01:01:02.000\n完\n\n"
+        );
+    }
+
+    #[test]
+    fn test_ass() {
+        let ass = subtitle().to_ass();
+        assert!(ass.starts_with("[Script Info]"));
+        let events = ass
+            .lines()
+            .filter(|l| l.starts_with("Dialogue:"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                "Dialogue: 0,0:00:00.50,0:00:02.80,Default,,0,0,0,,一样的月光",
+                "Dialogue: 0,0:00:03.00,0:00:05.25,Default,,0,0,0,,第一行\\N\\{二\\} & <三>",
+                "Dialogue: 0,1:01:01.13,1:01:02.00,Default,,0,0,0,,完",
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use md5::{Digest, Md5};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+// https://github.com/SocialSisterYi/bilibili-API-collect/blob/master/docs/misc/sign/wbi.md
+const MIXIN_KEY_ENC_TAB: [usize; 64] = [
+    46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42, 19, 29,
+    28, 14, 39, 12, 38, 41, 13, 37, 48, 7, 16, 24, 55, 40, 61, 26, 17, 0, 1, 60, 51, 30, 4, 22, 25,
+    54, 21, 56, 59, 6, 63, 57, 62, 11, 36, 20, 34, 44, 52,
+];
+
+// same as js `encodeURIComponent`
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+// keys rotate once a day, refetch well before that
+const KEYS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// img_key/sub_key pair from `/x/web-interface/nav`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WbiKeys {
+    img_key: String,
+    sub_key: String,
+}
+
+impl WbiKeys {
+    pub fn new(img_key: impl Into<String>, sub_key: impl Into<String>) -> Self {
+        Self {
+            img_key: img_key.into(),
+            sub_key: sub_key.into(),
+        }
+    }
+
+    /// keys are the file stem of `wbi_img.img_url`/`wbi_img.sub_url`
+    pub fn from_urls(img_url: &str, sub_url: &str) -> Option<Self> {
+        fn stem(url: &str) -> Option<&str> {
+            let name = url.rsplit('/').next()?;
+            let stem = name.split('.').next()?;
+            (!stem.is_empty()).then_some(stem)
+        }
+        Some(Self::new(stem(img_url)?, stem(sub_url)?))
+    }
+
+    pub fn mixin_key(&self) -> String {
+        let raw = format!("{}{}", self.img_key, self.sub_key);
+        let raw = raw.as_bytes();
+        MIXIN_KEY_ENC_TAB
+            .iter()
+            .filter_map(|&i| raw.get(i).map(|&c| c as char))
+            .take(32)
+            .collect()
+    }
+
+    /// sign with `wts` and return params with `w_rid` appended
+    pub fn sign<K, V>(
+        &self,
+        params: impl IntoIterator<Item = (K, V)>,
+        wts: u64,
+    ) -> Vec<(String, String)>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut params = params
+            .into_iter()
+            .map(|(k, v)| {
+                let v: String = v.into();
+                (k.into(), v.chars().filter(|c| !"!'()*".contains(*c)).collect())
+            })
+            .chain(std::iter::once(("wts".to_owned(), wts.to_string())))
+            .collect::<Vec<(String, String)>>();
+        params.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let query = params
+            .iter()
+            .map(|(k, v)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(k, COMPONENT),
+                    utf8_percent_encode(v, COMPONENT)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        let w_rid = Md5::digest(format!("{}{}", query, self.mixin_key()));
+        let w_rid = w_rid.iter().map(|b| format!("{:02x}", b)).collect();
+        params.push(("w_rid".to_owned(), w_rid));
+        params
+    }
+
+    pub fn sign_now<K, V>(&self, params: impl IntoIterator<Item = (K, V)>) -> Vec<(String, String)>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let wts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.sign(params, wts)
+    }
+}
+
+/// keys shared by every request of a `Service`
+#[derive(Debug, Default)]
+pub(crate) struct WbiCache {
+    inner: tokio::sync::RwLock<Option<(WbiKeys, Instant)>>,
+}
+
+impl WbiCache {
+    pub(crate) async fn get(&self) -> Option<WbiKeys> {
+        self.inner
+            .read()
+            .await
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < KEYS_TTL)
+            .map(|(keys, _)| keys.clone())
+    }
+
+    pub(crate) async fn set(&self, keys: WbiKeys) {
+        *self.inner.write().await = Some((keys, Instant::now()));
+    }
+
+    pub(crate) async fn invalidate(&self) {
+        *self.inner.write().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> WbiKeys {
+        WbiKeys::new(
+            "7cd084941338484aae1ad9425b84077c",
+            "4932caff0ff746eab6f01bf08b70ac45",
+        )
+    }
+
+    #[test]
+    fn test_from_urls() {
+        let keys = WbiKeys::from_urls(
+            "https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png",
+            "https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png",
+        );
+        assert_eq!(keys, Some(self::keys()));
+        assert_eq!(WbiKeys::from_urls("", "a.png"), None);
+    }
+
+    #[test]
+    fn test_mixin_key() {
+        assert_eq!(keys().mixin_key(), "ea1db124af3c7062474693fa704f4ff8");
+    }
+
+    #[test]
+    fn test_sign() {
+        let signed = keys().sign([("foo", "114"), ("bar", "514"), ("zab", "1919810")], 1702204169);
+        assert_eq!(
+            signed,
+            [
+                ("bar", "514"),
+                ("foo", "114"),
+                ("wts", "1702204169"),
+                ("zab", "1919810"),
+                ("w_rid", "8f6f2b5b3d485fe1886cec6a0be8c5d4"),
+            ]
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_sign_filter_and_encode() {
+        let a = keys().sign([("keyword", "a b!'()*")], 1702204169);
+        let b = keys().sign([("keyword", "a b")], 1702204169);
+        assert_eq!(a, b);
+        assert_eq!(a[0], ("keyword".to_owned(), "a b".to_owned()));
+    }
+}
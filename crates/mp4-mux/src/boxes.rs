@@ -0,0 +1,239 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use super::error::*;
+
+pub(crate) type FourCC = [u8; 4];
+
+pub(crate) fn fourcc_str(typ: &FourCC) -> String {
+    String::from_utf8_lossy(typ).into_owned()
+}
+
+/// top level box header read from a seekable input
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BoxHeader {
+    pub typ: FourCC,
+    /// offset of the first byte of the box
+    pub offset: u64,
+    /// total size including header
+    pub size: u64,
+}
+
+impl BoxHeader {
+    pub fn end(&self) -> u64 {
+        self.offset + self.size
+    }
+}
+
+/// read the box header at `offset`, `None` at eof
+pub(crate) fn read_header<R: Read + Seek>(
+    r: &mut R,
+    offset: u64,
+    eof: u64,
+) -> Result<Option<BoxHeader>> {
+    if offset >= eof {
+        return Ok(None);
+    }
+    r.seek(SeekFrom::Start(offset))?;
+    let mut head = [0u8; 8];
+    r.read_exact(&mut head)?;
+    let typ = [head[4], head[5], head[6], head[7]];
+    let (size, header_len) = match u32::from_be_bytes([head[0], head[1], head[2], head[3]]) {
+        0 => (eof - offset, 8),
+        1 => {
+            let mut large = [0u8; 8];
+            r.read_exact(&mut large)?;
+            (u64::from_be_bytes(large), 16)
+        }
+        size => (size as u64, 8),
+    };
+    if size < header_len || offset.checked_add(size).is_none_or(|end| end > eof) {
+        return Err(Error::InvalidBox(
+            fourcc_str(&typ),
+            format!("bad size {}", size),
+        ));
+    }
+    Ok(Some(BoxHeader { typ, offset, size }))
+}
+
+/// read the whole box (header included) into memory
+pub(crate) fn read_box<R: Read + Seek>(r: &mut R, header: &BoxHeader) -> Result<Vec<u8>> {
+    r.seek(SeekFrom::Start(header.offset))?;
+    let mut buf = vec![0u8; header.size as usize];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// a box parsed from memory
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Atom<'a> {
+    pub typ: FourCC,
+    /// the whole box, header included
+    pub raw: &'a [u8],
+    /// payload after the header
+    pub data: &'a [u8],
+}
+
+impl<'a> Atom<'a> {
+    pub fn children(&self) -> Atoms<'a> {
+        atoms(self.data)
+    }
+
+    pub fn child(&self, typ: &FourCC) -> Option<Atom<'a>> {
+        self.children()
+            .filter_map(|a| a.ok())
+            .find(|a| &a.typ == typ)
+    }
+
+    pub fn require(&self, typ: &FourCC) -> Result<Atom<'a>> {
+        self.child(typ).ok_or_else(|| {
+            Error::MissingBox(format!("{}/{}", fourcc_str(&self.typ), fourcc_str(typ)))
+        })
+    }
+
+    /// cursor over a full box payload, with version and flags
+    pub fn full(&self) -> Result<(u8, u32, Cursor<'a>)> {
+        let mut c = Cursor::new(self.typ, self.data);
+        let vf = c.u32()?;
+        Ok(((vf >> 24) as u8, vf & 0x00ff_ffff, c))
+    }
+}
+
+pub(crate) struct Atoms<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for Atoms<'a> {
+    type Item = Result<Atom<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.buf[self.pos..];
+        if rest.len() < 8 {
+            return None;
+        }
+        let typ = [rest[4], rest[5], rest[6], rest[7]];
+        let (size, header_len) = match u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) {
+            0 => (rest.len(), 8),
+            1 if rest.len() >= 16 => {
+                let mut large = [0u8; 8];
+                large.copy_from_slice(&rest[8..16]);
+                (u64::from_be_bytes(large) as usize, 16)
+            }
+            1 => (0, 16),
+            size => (size as usize, 8),
+        };
+        if size < header_len || size > rest.len() {
+            self.pos = self.buf.len();
+            return Some(Err(Error::InvalidBox(
+                fourcc_str(&typ),
+                format!("bad size {}", size),
+            )));
+        }
+        let atom = Atom {
+            typ,
+            raw: &rest[..size],
+            data: &rest[header_len..size],
+        };
+        self.pos += size;
+        Some(Ok(atom))
+    }
+}
+
+pub(crate) fn atoms(buf: &[u8]) -> Atoms<'_> {
+    Atoms { buf, pos: 0 }
+}
+
+/// big endian reader over a box payload
+pub(crate) struct Cursor<'a> {
+    typ: FourCC,
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(typ: FourCC, buf: &'a [u8]) -> Self {
+        Self { typ, buf, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos + n;
+        if end > self.buf.len() {
+            return Err(Error::InvalidBox(
+                fourcc_str(&self.typ),
+                "unexpected end".to_owned(),
+            ));
+        }
+        let s = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(s)
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<()> {
+        self.take(n).map(|_| ())
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        let b = self.take(8)?;
+        let mut a = [0u8; 8];
+        a.copy_from_slice(b);
+        Ok(u64::from_be_bytes(a))
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.buf[self.pos..]
+    }
+}
+
+/// append a box, `f` writes the payload
+pub(crate) fn write_box(out: &mut Vec<u8>, typ: &FourCC, f: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out.extend_from_slice(typ);
+    f(out);
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+/// append a full box with version and flags
+pub(crate) fn write_full_box(
+    out: &mut Vec<u8>,
+    typ: &FourCC,
+    version: u8,
+    flags: u32,
+    f: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, typ, |out| {
+        out.extend_from_slice(&(((version as u32) << 24) | (flags & 0x00ff_ffff)).to_be_bytes());
+        f(out);
+    })
+}
+
+pub(crate) trait PutBE {
+    fn put_u16(&mut self, v: u16);
+    fn put_u32(&mut self, v: u32);
+    fn put_u64(&mut self, v: u64);
+}
+
+impl PutBE for Vec<u8> {
+    fn put_u16(&mut self, v: u16) {
+        self.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn put_u32(&mut self, v: u32) {
+        self.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn put_u64(&mut self, v: u64) {
+        self.extend_from_slice(&v.to_be_bytes());
+    }
+}
@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("io error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("invalid box {0}: {1}")]
+    InvalidBox(String, String),
+    #[error("missing box: {0}")]
+    MissingBox(String),
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,612 @@
+//! remux fragmented mp4 tracks (e.g. bilibili dash `.m4s`) into one progressive mp4
+
+mod boxes;
+mod error;
+mod metadata;
+mod track;
+
+pub use error::*;
+pub use metadata::{Cover, CoverFormat, Metadata};
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use boxes::*;
+use metadata::udta;
+use track::*;
+
+const MOVIE_TIMESCALE: u32 = 1000;
+const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// remux the first track of every input into `out`, tracks are numbered in input order
+pub fn remux<R, W>(inputs: &mut [R], out: &mut W) -> Result<()>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    remux_with(inputs, out, &Metadata::default())
+}
+
+/// `remux` and tag the movie with `metadata`
+pub fn remux_with<R, W>(inputs: &mut [R], out: &mut W, metadata: &Metadata) -> Result<()>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    if inputs.is_empty() {
+        return Err(Error::Unsupported("no input track".to_owned()));
+    }
+    let tracks = inputs
+        .iter_mut()
+        .map(InputTrack::read)
+        .collect::<Result<Vec<_>>>()?;
+
+    // --- interleave chunks by decode time
+    let mut order = tracks
+        .iter()
+        .enumerate()
+        .flat_map(|(t, track)| (0..track.chunks.len()).map(move |c| (t, c)))
+        .collect::<Vec<_>>();
+    order.sort_by(|&(ta, ca), &(tb, cb)| {
+        let (a, b) = (&tracks[ta], &tracks[tb]);
+        let ka = a.chunks[ca].dts as u128 * b.timescale as u128;
+        let kb = b.chunks[cb].dts as u128 * a.timescale as u128;
+        ka.cmp(&kb).then(ta.cmp(&tb))
+    });
+
+    // --- layout: ftyp | moov | mdat
+    let data_len: u64 = order.iter().map(|&(t, c)| tracks[t].chunks[c].len).sum();
+    let mdat_header_len: u64 = if data_len + 8 > u32::MAX as u64 {
+        16
+    } else {
+        8
+    };
+    let ftyp = ftyp();
+    // moov size only depends on whether chunk offsets need 64 bits
+    let moov_len = |large| moov(&tracks, &order, 0, large, metadata).len() as u64;
+    let large = ftyp.len() as u64 + moov_len(false) + mdat_header_len + data_len > u32::MAX as u64;
+    let data_start = ftyp.len() as u64 + moov_len(large) + mdat_header_len;
+    let moov = moov(&tracks, &order, data_start, large, metadata);
+
+    out.write_all(&ftyp)?;
+    out.write_all(&moov)?;
+    if mdat_header_len == 16 {
+        out.write_all(&1u32.to_be_bytes())?;
+        out.write_all(b"mdat")?;
+        out.write_all(&(data_len + 16).to_be_bytes())?;
+    } else {
+        out.write_all(&((data_len + 8) as u32).to_be_bytes())?;
+        out.write_all(b"mdat")?;
+    }
+    for &(t, c) in order.iter() {
+        let chunk = tracks[t].chunks[c];
+        let input = &mut inputs[t];
+        input.seek(SeekFrom::Start(chunk.offset))?;
+        let copied = std::io::copy(&mut input.by_ref().take(chunk.len), out)?;
+        if copied != chunk.len {
+            return Err(Error::InvalidBox(
+                "mdat".to_owned(),
+                "truncated sample data".to_owned(),
+            ));
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn ftyp() -> Vec<u8> {
+    let mut out = vec![];
+    write_box(&mut out, b"ftyp", |out| {
+        out.extend_from_slice(b"isom");
+        out.put_u32(0x200);
+        for brand in [b"isom", b"iso2", b"avc1", b"mp41"] {
+            out.extend_from_slice(brand);
+        }
+    });
+    out
+}
+
+fn to_movie_time(v: u64, timescale: u32) -> u64 {
+    (v as u128 * MOVIE_TIMESCALE as u128 / timescale as u128) as u64
+}
+
+/// presentation window of a track, every input starts at 0 like `ffmpeg -c copy`
+struct Presentation {
+    /// composition time of the first presented sample, in media timescale
+    media_time: u64,
+    /// in movie timescale
+    duration: u64,
+}
+
+impl Presentation {
+    fn new(track: &InputTrack) -> Self {
+        let mut dts = 0i64;
+        let mut first = i64::MAX;
+        for s in track.samples.iter() {
+            first = first.min(dts + s.cto as i64);
+            dts += s.duration as i64;
+        }
+        let media_time = first.max(0) as u64;
+        Self {
+            media_time,
+            duration: to_movie_time(track.duration().saturating_sub(media_time), track.timescale),
+        }
+    }
+}
+
+fn moov(
+    tracks: &[InputTrack],
+    order: &[(usize, usize)],
+    data_start: u64,
+    large: bool,
+    metadata: &Metadata,
+) -> Vec<u8> {
+    // --- output chunk offsets per track
+    let mut offsets = vec![vec![]; tracks.len()];
+    let mut pos = data_start;
+    for &(t, c) in order.iter() {
+        offsets[t].push((c, pos));
+        pos += tracks[t].chunks[c].len;
+    }
+    for o in offsets.iter_mut() {
+        o.sort();
+    }
+
+    let presentations = tracks.iter().map(Presentation::new).collect::<Vec<_>>();
+    let movie_duration = presentations.iter().map(|p| p.duration).max().unwrap_or(0);
+
+    let mut out = vec![];
+    write_box(&mut out, b"moov", |out| {
+        let version = if movie_duration > u32::MAX as u64 {
+            1
+        } else {
+            0
+        };
+        write_full_box(out, b"mvhd", version, 0, |out| {
+            if version == 1 {
+                out.put_u64(0);
+                out.put_u64(0);
+                out.put_u32(MOVIE_TIMESCALE);
+                out.put_u64(movie_duration);
+            } else {
+                out.put_u32(0);
+                out.put_u32(0);
+                out.put_u32(MOVIE_TIMESCALE);
+                out.put_u32(movie_duration as u32);
+            }
+            out.put_u32(0x0001_0000);
+            out.put_u16(0x0100);
+            out.extend_from_slice(&[0; 10]);
+            MATRIX.iter().for_each(|&v| out.put_u32(v));
+            out.extend_from_slice(&[0; 24]);
+            out.put_u32(tracks.len() as u32 + 1);
+        });
+        for (i, (track, p)) in tracks.iter().zip(presentations.iter()).enumerate() {
+            let offsets = offsets[i].iter().map(|&(_, o)| o).collect::<Vec<_>>();
+            trak(out, track, i as u32 + 1, p, &offsets, large);
+        }
+        udta(out, metadata);
+    });
+    out
+}
+
+fn trak(
+    out: &mut Vec<u8>,
+    track: &InputTrack,
+    track_id: u32,
+    p: &Presentation,
+    offsets: &[u64],
+    large: bool,
+) {
+    write_box(out, b"trak", |out| {
+        let duration = p.duration;
+        let version = if duration > u32::MAX as u64 { 1 } else { 0 };
+        // enabled | in movie | in preview
+        write_full_box(out, b"tkhd", version, 0x7, |out| {
+            if version == 1 {
+                out.put_u64(0);
+                out.put_u64(0);
+                out.put_u32(track_id);
+                out.put_u32(0);
+                out.put_u64(duration);
+            } else {
+                out.put_u32(0);
+                out.put_u32(0);
+                out.put_u32(track_id);
+                out.put_u32(0);
+                out.put_u32(duration as u32);
+            }
+            out.extend_from_slice(&[0; 8]);
+            out.put_u16(0);
+            out.put_u16(0);
+            out.put_u16(if track.is_video() { 0 } else { track.volume });
+            out.put_u16(0);
+            MATRIX.iter().for_each(|&v| out.put_u32(v));
+            out.put_u32(track.width);
+            out.put_u32(track.height);
+        });
+
+        // --- edit list, skip the composition offset of the first frame
+        write_box(out, b"edts", |out| {
+            let wide = p.media_time > i32::MAX as u64 || duration > u32::MAX as u64;
+            write_full_box(out, b"elst", wide as u8, 0, |out| {
+                out.put_u32(1);
+                if wide {
+                    out.put_u64(duration);
+                    out.put_u64(p.media_time);
+                } else {
+                    out.put_u32(duration as u32);
+                    out.put_u32(p.media_time as u32);
+                }
+                // media_rate 1.0
+                out.put_u16(1);
+                out.put_u16(0);
+            });
+        });
+
+        write_box(out, b"mdia", |out| {
+            let media_duration = track.duration();
+            let version = if media_duration > u32::MAX as u64 {
+                1
+            } else {
+                0
+            };
+            write_full_box(out, b"mdhd", version, 0, |out| {
+                if version == 1 {
+                    out.put_u64(0);
+                    out.put_u64(0);
+                    out.put_u32(track.timescale);
+                    out.put_u64(media_duration);
+                } else {
+                    out.put_u32(0);
+                    out.put_u32(0);
+                    out.put_u32(track.timescale);
+                    out.put_u32(media_duration as u32);
+                }
+                out.put_u16(track.language);
+                out.put_u16(0);
+            });
+            out.extend_from_slice(&track.hdlr);
+            write_box(out, b"minf", |out| {
+                out.extend_from_slice(&track.media_header);
+                write_box(out, b"dinf", |out| {
+                    write_full_box(out, b"dref", 0, 0, |out| {
+                        out.put_u32(1);
+                        // self contained
+                        write_full_box(out, b"url ", 0, 1, |_| {});
+                    });
+                });
+                stbl(out, track, offsets, large);
+            });
+        });
+    });
+}
+
+fn stbl(out: &mut Vec<u8>, track: &InputTrack, offsets: &[u64], large: bool) {
+    write_box(out, b"stbl", |out| {
+        out.extend_from_slice(&track.stsd);
+
+        // --- stts
+        let mut stts: Vec<(u32, u32)> = vec![];
+        for s in track.samples.iter() {
+            match stts.last_mut() {
+                Some((count, delta)) if *delta == s.duration => *count += 1,
+                _ => stts.push((1, s.duration)),
+            }
+        }
+        write_full_box(out, b"stts", 0, 0, |out| {
+            out.put_u32(stts.len() as u32);
+            stts.iter().for_each(|&(count, delta)| {
+                out.put_u32(count);
+                out.put_u32(delta);
+            });
+        });
+
+        // --- ctts
+        if track.samples.iter().any(|s| s.cto != 0) {
+            let mut ctts: Vec<(u32, i32)> = vec![];
+            for s in track.samples.iter() {
+                match ctts.last_mut() {
+                    Some((count, cto)) if *cto == s.cto => *count += 1,
+                    _ => ctts.push((1, s.cto)),
+                }
+            }
+            let version = ctts.iter().any(|&(_, cto)| cto < 0) as u8;
+            write_full_box(out, b"ctts", version, 0, |out| {
+                out.put_u32(ctts.len() as u32);
+                ctts.iter().for_each(|&(count, cto)| {
+                    out.put_u32(count);
+                    out.put_u32(cto as u32);
+                });
+            });
+        }
+
+        // --- stss, absent means every sample is sync
+        if track.samples.iter().any(|s| !s.sync) {
+            let sync = track
+                .samples
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.sync)
+                .map(|(i, _)| i as u32 + 1)
+                .collect::<Vec<_>>();
+            write_full_box(out, b"stss", 0, 0, |out| {
+                out.put_u32(sync.len() as u32);
+                sync.iter().for_each(|&n| out.put_u32(n));
+            });
+        }
+
+        // --- stsc
+        let mut stsc: Vec<(u32, u32)> = vec![];
+        for (i, chunk) in track.chunks.iter().enumerate() {
+            match stsc.last() {
+                Some(&(_, count)) if count == chunk.sample_count as u32 => {}
+                _ => stsc.push((i as u32 + 1, chunk.sample_count as u32)),
+            }
+        }
+        write_full_box(out, b"stsc", 0, 0, |out| {
+            out.put_u32(stsc.len() as u32);
+            stsc.iter().for_each(|&(first, count)| {
+                out.put_u32(first);
+                out.put_u32(count);
+                out.put_u32(1);
+            });
+        });
+
+        // --- stsz
+        write_full_box(out, b"stsz", 0, 0, |out| {
+            out.put_u32(0);
+            out.put_u32(track.samples.len() as u32);
+            track.samples.iter().for_each(|s| out.put_u32(s.size));
+        });
+
+        // --- stco/co64
+        if large {
+            write_full_box(out, b"co64", 0, 0, |out| {
+                out.put_u32(offsets.len() as u32);
+                offsets.iter().for_each(|&o| out.put_u64(o));
+            });
+        } else {
+            write_full_box(out, b"stco", 0, 0, |out| {
+                out.put_u32(offsets.len() as u32);
+                offsets.iter().for_each(|&o| out.put_u32(o as u32));
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIDEO: &[u8] = include_bytes!("../tests/fixtures/video.m4s");
+    const AUDIO: &[u8] = include_bytes!("../tests/fixtures/audio.m4s");
+
+    fn remux_fixtures() -> Result<Vec<u8>> {
+        let mut inputs = [std::io::Cursor::new(VIDEO), std::io::Cursor::new(AUDIO)];
+        let mut out = vec![];
+        remux(&mut inputs, &mut out)?;
+        Ok(out)
+    }
+
+    fn u32_at(b: &[u8], i: usize) -> u32 {
+        u32::from_be_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]])
+    }
+
+    /// `(count, entries)` of a full box table with `width` u32 per entry
+    fn table(stbl: &Atom, typ: &FourCC, width: usize) -> Vec<Vec<u32>> {
+        let data = stbl.require(typ).unwrap().data;
+        let count = u32_at(data, 4) as usize;
+        (0..count)
+            .map(|i| {
+                (0..width)
+                    .map(|j| u32_at(data, 8 + (i * width + j) * 4))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// sample payloads of a track resolved through stsc/stco/stsz
+    fn samples(file: &[u8], stbl: &Atom) -> Vec<Vec<u8>> {
+        let stsz = stbl.require(b"stsz").unwrap().data;
+        let sizes = (0..u32_at(stsz, 8) as usize)
+            .map(|i| u32_at(stsz, 12 + i * 4) as usize)
+            .collect::<Vec<_>>();
+        let offsets = table(stbl, b"stco", 1);
+        let stsc = table(stbl, b"stsc", 3);
+        let mut res = vec![];
+        for (i, offset) in offsets.iter().enumerate() {
+            let chunk = i as u32 + 1;
+            let per_chunk = stsc.iter().rev().find(|e| e[0] <= chunk).unwrap()[1];
+            let mut pos = offset[0] as usize;
+            for _ in 0..per_chunk {
+                let size = sizes[res.len()];
+                res.push(file[pos..pos + size].to_vec());
+                pos += size;
+            }
+        }
+        assert_eq!(res.len(), sizes.len());
+        res
+    }
+
+    #[test]
+    fn test_remux_layout() -> Result<()> {
+        let out = remux_fixtures()?;
+        let top = atoms(&out).collect::<Result<Vec<_>>>()?;
+        let types = top.iter().map(|a| &a.typ).collect::<Vec<_>>();
+        assert_eq!(types, [b"ftyp", b"moov", b"mdat"]);
+
+        let moov = top[1];
+        let mvhd = moov.require(b"mvhd")?.data;
+        assert_eq!(u32_at(mvhd, 12), MOVIE_TIMESCALE);
+        let traks = moov
+            .children()
+            .filter_map(|a| a.ok())
+            .filter(|a| &a.typ == b"trak")
+            .collect::<Vec<_>>();
+        assert_eq!(traks.len(), 2);
+
+        // --- video: 15360 timescale, 8 samples of 512, b-frames start at 1024
+        let mdia = traks[0].require(b"mdia")?;
+        let mdhd = mdia.require(b"mdhd")?.data;
+        assert_eq!((u32_at(mdhd, 12), u32_at(mdhd, 16)), (15360, 4096));
+        assert_eq!(&mdia.require(b"hdlr")?.data[8..12], b"vide");
+        let stbl = mdia.require(b"minf")?.require(b"stbl")?;
+        assert_eq!(&stbl.require(b"stsd")?.data[12..16], b"avc1");
+        assert_eq!(table(&stbl, b"stts", 2), [vec![8, 512]]);
+        assert_eq!(table(&stbl, b"stss", 1), [vec![1], vec![5]]);
+        assert_eq!(
+            table(&stbl, b"ctts", 2),
+            [
+                [1, 1024],
+                [1, 2048],
+                [1, 512],
+                [2, 1024],
+                [1, 2048],
+                [1, 512],
+                [1, 1024]
+            ]
+            .map(|e| e.to_vec())
+        );
+        assert_eq!(table(&stbl, b"stsc", 3), [vec![1, 4, 1]]);
+        let elst = traks[0].require(b"edts")?.require(b"elst")?.data;
+        // 4096 - 1024 ticks at 15360 => 200ms
+        assert_eq!(
+            (u32_at(elst, 4), u32_at(elst, 8), u32_at(elst, 12)),
+            (1, 200, 1024)
+        );
+        let tkhd = traks[0].require(b"tkhd")?.data;
+        assert_eq!((u32_at(tkhd, 76), u32_at(tkhd, 80)), (64 << 16, 48 << 16));
+
+        // --- audio: 44100 timescale, 15 sync samples of 1024
+        let mdia = traks[1].require(b"mdia")?;
+        let mdhd = mdia.require(b"mdhd")?.data;
+        assert_eq!((u32_at(mdhd, 12), u32_at(mdhd, 16)), (44100, 15 * 1024));
+        let stbl = mdia.require(b"minf")?.require(b"stbl")?;
+        assert!(stbl.child(b"stss").is_none());
+        assert!(stbl.child(b"ctts").is_none());
+        assert_eq!(table(&stbl, b"stsc", 3), [vec![1, 5, 1]]);
+        let elst = traks[1].require(b"edts")?.require(b"elst")?.data;
+        assert_eq!(
+            (u32_at(elst, 4), u32_at(elst, 8), u32_at(elst, 12)),
+            (1, 348, 0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_remux_samples() -> Result<()> {
+        let out = remux_fixtures()?;
+        let moov = atoms(&out).nth(1).unwrap()?;
+        let traks = moov
+            .children()
+            .filter_map(|a| a.ok())
+            .filter(|a| &a.typ == b"trak")
+            .collect::<Vec<_>>();
+        let stbl = |t: &Atom<'_>| -> Result<Vec<Vec<u8>>> {
+            let stbl = t.require(b"mdia")?.require(b"minf")?.require(b"stbl")?;
+            Ok(samples(&out, &stbl))
+        };
+
+        let expect = (0..2u8)
+            .flat_map(|f| (0..4u8).map(move |i| [0x56, f, i].repeat(10 + i as usize)))
+            .collect::<Vec<_>>();
+        assert_eq!(stbl(&traks[0])?, expect);
+        let expect = (0..3u8)
+            .flat_map(|f| (0..5u8).map(move |i| [0x41, f, i].repeat(5 + i as usize)))
+            .collect::<Vec<_>>();
+        assert_eq!(stbl(&traks[1])?, expect);
+
+        // interleaved by decode time: v0 a0 a1 v1 a2
+        let first = |t: &Atom<'_>| -> Result<Vec<u32>> {
+            let stbl = t.require(b"mdia")?.require(b"minf")?.require(b"stbl")?;
+            Ok(table(&stbl, b"stco", 1).into_iter().map(|e| e[0]).collect())
+        };
+        let (v, a) = (first(&traks[0])?, first(&traks[1])?);
+        assert!(v[0] < a[0] && a[0] < a[1] && a[1] < v[1] && v[1] < a[2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remux_single_track() -> Result<()> {
+        let mut inputs = [std::io::Cursor::new(VIDEO)];
+        let mut out = vec![];
+        remux(&mut inputs, &mut out)?;
+        let moov = atoms(&out).nth(1).unwrap()?;
+        let traks = moov
+            .children()
+            .filter_map(|a| a.ok())
+            .filter(|a| &a.typ == b"trak");
+        assert_eq!(traks.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remux_cover() -> Result<()> {
+        let jpeg = [0xff, 0xd8, 0xff, 0xe0, 1, 2, 3];
+        assert!(Cover::new(b"RIFF....WEBP".to_vec()).is_none());
+        let metadata = Metadata {
+            cover: Cover::new(jpeg.to_vec()),
+        };
+        let mut inputs = [std::io::Cursor::new(VIDEO), std::io::Cursor::new(AUDIO)];
+        let mut out = vec![];
+        remux_with(&mut inputs, &mut out, &metadata)?;
+
+        let moov = atoms(&out).nth(1).unwrap()?;
+        let meta = moov.require(b"udta")?.require(b"meta")?;
+        // full box, children after version and flags
+        let children = atoms(&meta.data[4..]).collect::<Result<Vec<_>>>()?;
+        assert_eq!(&children[0].data[8..12], b"mdir");
+        let data = children[1].require(b"covr")?.require(b"data")?.data;
+        assert_eq!(u32_at(data, 0), 13);
+        assert_eq!(&data[8..], jpeg);
+
+        // samples still point into mdat after the larger moov
+        let plain = remux_fixtures()?;
+        assert_eq!(
+            out.len(),
+            plain.len() + moov.raw.len() - atoms(&plain).nth(1).unwrap()?.raw.len()
+        );
+        let trak = moov.require(b"trak")?;
+        let stbl = trak.require(b"mdia")?.require(b"minf")?.require(b"stbl")?;
+        let expect = (0..2u8)
+            .flat_map(|f| (0..4u8).map(move |i| [0x56, f, i].repeat(10 + i as usize)))
+            .collect::<Vec<_>>();
+        assert_eq!(samples(&out, &stbl), expect);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remux_invalid() {
+        let mut inputs = [std::io::Cursor::new(&AUDIO[..24])];
+        assert!(remux(&mut inputs, &mut vec![]).is_err());
+
+        // ftyp only, no moov
+        let ftyp = u32_at(AUDIO, 0) as usize;
+        let mut inputs = [std::io::Cursor::new(&AUDIO[..ftyp])];
+        assert!(matches!(
+            remux(&mut inputs, &mut vec![]),
+            Err(Error::MissingBox(_))
+        ));
+
+        // a largesize box running past u64::MAX
+        let mut huge = AUDIO[..ftyp].to_vec();
+        huge.extend_from_slice(&1u32.to_be_bytes());
+        huge.extend_from_slice(b"free");
+        huge.extend_from_slice(&u64::MAX.to_be_bytes());
+        let mut inputs = [std::io::Cursor::new(&huge[..])];
+        assert!(matches!(
+            remux(&mut inputs, &mut vec![]),
+            Err(Error::InvalidBox(..))
+        ));
+
+        // the second fragment decodes before the first, tfdt is version 1
+        let mut video = VIDEO.to_vec();
+        let tfdt = video.windows(4).position(|w| w == b"tfdt").unwrap();
+        video[tfdt + 8..tfdt + 16].copy_from_slice(&0x1000u64.to_be_bytes());
+        let mut inputs = [std::io::Cursor::new(&video[..])];
+        assert!(matches!(
+            remux(&mut inputs, &mut vec![]),
+            Err(Error::InvalidBox(..))
+        ));
+    }
+}
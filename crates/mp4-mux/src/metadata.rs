@@ -0,0 +1,73 @@
+use super::boxes::*;
+
+/// itunes style tags written into `moov/udta/meta/ilst`
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    /// `covr`, shown as thumbnail by players and file managers
+    pub cover: Option<Cover>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cover {
+    pub format: CoverFormat,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverFormat {
+    Jpeg,
+    Png,
+}
+
+impl Cover {
+    /// detect the format from magic bytes, only jpeg and png can be embedded
+    pub fn new(data: Vec<u8>) -> Option<Self> {
+        let format = match data.as_slice() {
+            [0xff, 0xd8, 0xff, ..] => CoverFormat::Jpeg,
+            [0x89, b'P', b'N', b'G', ..] => CoverFormat::Png,
+            _ => return None,
+        };
+        Some(Self { format, data })
+    }
+}
+
+impl Metadata {
+    fn is_empty(&self) -> bool {
+        self.cover.is_none()
+    }
+}
+
+/// `udta` of the movie, nothing when there is no tag
+pub(crate) fn udta(out: &mut Vec<u8>, metadata: &Metadata) {
+    if metadata.is_empty() {
+        return;
+    }
+    write_box(out, b"udta", |out| {
+        write_full_box(out, b"meta", 0, 0, |out| {
+            write_full_box(out, b"hdlr", 0, 0, |out| {
+                out.put_u32(0);
+                out.extend_from_slice(b"mdir");
+                out.extend_from_slice(b"appl");
+                out.extend_from_slice(&[0; 8]);
+                // empty name
+                out.push(0);
+            });
+            write_box(out, b"ilst", |out| {
+                if let Some(cover) = &metadata.cover {
+                    write_box(out, b"covr", |out| {
+                        write_box(out, b"data", |out| {
+                            // well-known types of the itunes metadata spec
+                            out.put_u32(match cover.format {
+                                CoverFormat::Jpeg => 13,
+                                CoverFormat::Png => 14,
+                            });
+                            // locale
+                            out.put_u32(0);
+                            out.extend_from_slice(&cover.data);
+                        });
+                    });
+                }
+            });
+        });
+    });
+}
@@ -0,0 +1,330 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use super::boxes::*;
+use super::error::*;
+
+// sample_is_non_sync_sample in sample flags
+const NON_SYNC: u32 = 0x0001_0000;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Sample {
+    pub size: u32,
+    pub duration: u32,
+    pub sync: bool,
+    pub cto: i32,
+}
+
+/// contiguous samples of one `trun`, copied as a single chunk
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Chunk {
+    pub sample_count: usize,
+    /// offset in the input file
+    pub offset: u64,
+    pub len: u64,
+    /// decode time of the first sample, relative to the track start
+    pub dts: u64,
+}
+
+/// first track of a fragmented mp4
+#[derive(Debug)]
+pub(crate) struct InputTrack {
+    pub handler: FourCC,
+    pub timescale: u32,
+    pub language: u16,
+    pub width: u32,
+    pub height: u32,
+    pub volume: u16,
+    /// raw `hdlr`, `vmhd`/`smhd` and `stsd` boxes, copied as is
+    pub hdlr: Vec<u8>,
+    pub media_header: Vec<u8>,
+    pub stsd: Vec<u8>,
+    /// `tfdt` of the first fragment
+    base_dts: u64,
+    pub samples: Vec<Sample>,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Defaults {
+    duration: u32,
+    size: u32,
+    flags: u32,
+}
+
+impl InputTrack {
+    pub fn read<R: Read + Seek>(r: &mut R) -> Result<Self> {
+        let eof = r.seek(SeekFrom::End(0))?;
+        let mut offset = 0;
+        let mut track: Option<(InputTrack, u32, Defaults)> = None;
+        let mut next_dts = None;
+        while let Some(header) = read_header(r, offset, eof)? {
+            offset = header.end();
+            match &header.typ {
+                b"moov" => {
+                    let buf = read_box(r, &header)?;
+                    track = Some(Self::parse_moov(&buf)?);
+                }
+                b"moof" => {
+                    let (track, track_id, defaults) = track
+                        .as_mut()
+                        .ok_or_else(|| Error::MissingBox("moov".to_owned()))?;
+                    let buf = read_box(r, &header)?;
+                    track.parse_moof(&buf, header.offset, *track_id, *defaults, &mut next_dts)?;
+                }
+                _ => {}
+            }
+        }
+        let (track, _, _) = track.ok_or_else(|| Error::MissingBox("moov".to_owned()))?;
+        if track.samples.is_empty() {
+            return Err(Error::MissingBox("moof".to_owned()));
+        }
+        Ok(track)
+    }
+
+    fn parse_moov(buf: &[u8]) -> Result<(Self, u32, Defaults)> {
+        let moov = atoms(buf)
+            .next()
+            .ok_or_else(|| Error::MissingBox("moov".to_owned()))??;
+        let trak = moov.require(b"trak")?;
+
+        // --- tkhd
+        let tkhd = trak.require(b"tkhd")?;
+        let (version, _, mut c) = tkhd.full()?;
+        c.skip(if version == 1 { 16 } else { 8 })?;
+        let track_id = c.u32()?;
+        let tail = c.remaining();
+        if tail.len() < 8 + 36 + 4 {
+            return Err(Error::InvalidBox("tkhd".to_owned(), "too short".to_owned()));
+        }
+        let n = tail.len();
+        let width = u32::from_be_bytes([tail[n - 8], tail[n - 7], tail[n - 6], tail[n - 5]]);
+        let height = u32::from_be_bytes([tail[n - 4], tail[n - 3], tail[n - 2], tail[n - 1]]);
+        let volume = u16::from_be_bytes([tail[n - 48], tail[n - 47]]);
+
+        // --- mdia
+        let mdia = trak.require(b"mdia")?;
+        let (version, _, mut c) = mdia.require(b"mdhd")?.full()?;
+        let timescale = match version {
+            1 => {
+                c.skip(16)?;
+                let ts = c.u32()?;
+                c.skip(8)?;
+                ts
+            }
+            _ => {
+                c.skip(8)?;
+                let ts = c.u32()?;
+                c.skip(4)?;
+                ts
+            }
+        };
+        let language = c.u16()?;
+        if timescale == 0 {
+            return Err(Error::InvalidBox(
+                "mdhd".to_owned(),
+                "zero timescale".to_owned(),
+            ));
+        }
+        let hdlr = mdia.require(b"hdlr")?;
+        let (_, _, mut c) = hdlr.full()?;
+        c.skip(4)?;
+        let handler = {
+            let h = c.u32()?.to_be_bytes();
+            [h[0], h[1], h[2], h[3]]
+        };
+        let minf = mdia.require(b"minf")?;
+        let media_header = minf
+            .children()
+            .filter_map(|a| a.ok())
+            .find(|a| matches!(&a.typ, b"vmhd" | b"smhd" | b"nmhd" | b"sthd"))
+            .ok_or_else(|| Error::MissingBox("minf/vmhd".to_owned()))?;
+        let stsd = minf.require(b"stbl")?.require(b"stsd")?;
+
+        // --- mvex/trex
+        let defaults = moov
+            .child(b"mvex")
+            .into_iter()
+            .flat_map(|mvex| mvex.children().filter_map(|a| a.ok()).collect::<Vec<_>>())
+            .filter(|a| &a.typ == b"trex")
+            .map(|trex| -> Result<Option<Defaults>> {
+                let (_, _, mut c) = trex.full()?;
+                if c.u32()? != track_id {
+                    return Ok(None);
+                }
+                c.skip(4)?;
+                Ok(Some(Defaults {
+                    duration: c.u32()?,
+                    size: c.u32()?,
+                    flags: c.u32()?,
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .next()
+            .unwrap_or_default();
+
+        Ok((
+            Self {
+                handler,
+                timescale,
+                language,
+                width,
+                height,
+                volume,
+                hdlr: hdlr.raw.to_vec(),
+                media_header: media_header.raw.to_vec(),
+                stsd: stsd.raw.to_vec(),
+                base_dts: 0,
+                samples: vec![],
+                chunks: vec![],
+            },
+            track_id,
+            defaults,
+        ))
+    }
+
+    fn parse_moof(
+        &mut self,
+        buf: &[u8],
+        moof_offset: u64,
+        track_id: u32,
+        trex: Defaults,
+        next_dts: &mut Option<u64>,
+    ) -> Result<()> {
+        let moof = atoms(buf)
+            .next()
+            .ok_or_else(|| Error::MissingBox("moof".to_owned()))??;
+        for traf in moof.children() {
+            let traf = traf?;
+            if &traf.typ != b"traf" {
+                continue;
+            }
+
+            // --- tfhd
+            let (_, flags, mut c) = traf.require(b"tfhd")?.full()?;
+            if c.u32()? != track_id {
+                continue;
+            }
+            let base = if flags & 0x1 != 0 {
+                c.u64()?
+            } else {
+                moof_offset
+            };
+            if flags & 0x2 != 0 {
+                c.skip(4)?;
+            }
+            let defaults = Defaults {
+                duration: if flags & 0x8 != 0 {
+                    c.u32()?
+                } else {
+                    trex.duration
+                },
+                size: if flags & 0x10 != 0 {
+                    c.u32()?
+                } else {
+                    trex.size
+                },
+                flags: if flags & 0x20 != 0 {
+                    c.u32()?
+                } else {
+                    trex.flags
+                },
+            };
+
+            // --- tfdt
+            let mut dts = match traf.child(b"tfdt") {
+                Some(tfdt) => {
+                    let (version, _, mut c) = tfdt.full()?;
+                    if version == 1 {
+                        c.u64()?
+                    } else {
+                        c.u32()? as u64
+                    }
+                }
+                None => next_dts.unwrap_or(0),
+            };
+            if next_dts.is_none() {
+                self.base_dts = dts;
+            }
+
+            // --- trun
+            let mut data_offset = base;
+            for trun in traf.children() {
+                let trun = trun?;
+                if &trun.typ != b"trun" {
+                    continue;
+                }
+                let (_, flags, mut c) = trun.full()?;
+                let count = c.u32()? as usize;
+                if flags & 0x1 != 0 {
+                    data_offset = base.wrapping_add_signed(c.u32()? as i32 as i64);
+                }
+                let first_flags = if flags & 0x4 != 0 {
+                    Some(c.u32()?)
+                } else {
+                    None
+                };
+
+                let chunk = Chunk {
+                    sample_count: count,
+                    offset: data_offset,
+                    len: 0,
+                    dts: dts.checked_sub(self.base_dts).ok_or_else(|| {
+                        Error::InvalidBox("tfdt".to_owned(), "decreasing".to_owned())
+                    })?,
+                };
+                let mut len = 0u64;
+                for i in 0..count {
+                    let duration = if flags & 0x100 != 0 {
+                        c.u32()?
+                    } else {
+                        defaults.duration
+                    };
+                    let size = if flags & 0x200 != 0 {
+                        c.u32()?
+                    } else {
+                        defaults.size
+                    };
+                    let sample_flags = if flags & 0x400 != 0 {
+                        c.u32()?
+                    } else {
+                        defaults.flags
+                    };
+                    let sample_flags = match (i, first_flags) {
+                        (0, Some(first)) => first,
+                        _ => sample_flags,
+                    };
+                    // signed in version 1, and in practice in version 0 too
+                    let cto = if flags & 0x800 != 0 {
+                        c.u32()? as i32
+                    } else {
+                        0
+                    };
+                    self.samples.push(Sample {
+                        size,
+                        duration,
+                        sync: sample_flags & NON_SYNC == 0,
+                        cto,
+                    });
+                    len += size as u64;
+                    dts += duration as u64;
+                }
+                if count > 0 {
+                    self.chunks.push(Chunk { len, ..chunk });
+                }
+                data_offset += len;
+            }
+            *next_dts = Some(dts);
+        }
+        Ok(())
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.samples.iter().map(|s| s.duration as u64).sum()
+    }
+
+    pub fn is_video(&self) -> bool {
+        &self.handler == b"vide"
+    }
+}
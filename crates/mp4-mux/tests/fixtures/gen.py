@@ -0,0 +1,101 @@
+#!/usr/bin/env python3
+"""generate the synthetic fragmented mp4 fixtures used by the unit tests"""
+import struct
+
+
+def box(typ, *payload):
+    data = b"".join(payload)
+    return struct.pack(">I", 8 + len(data)) + typ + data
+
+
+def full(typ, version, flags, *payload):
+    return box(typ, struct.pack(">I", (version << 24) | flags), *payload)
+
+
+MATRIX = struct.pack(">9I", 0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000)
+
+
+def moov(handler, timescale, width, height, media_header, sample_entry, trex_flags):
+    tkhd = full(b"tkhd", 0, 3, struct.pack(">IIIII", 0, 0, 1, 0, 0), bytes(8),
+                struct.pack(">HHHH", 0, 0, 0 if handler == b"vide" else 0x100, 0),
+                MATRIX, struct.pack(">II", width << 16, height << 16))
+    mdhd = full(b"mdhd", 0, 0, struct.pack(">IIIIHH", 0, 0, timescale, 0, 0x55c4, 0))
+    hdlr = full(b"hdlr", 0, 0, struct.pack(">I", 0), handler, bytes(12), b"fixture\0")
+    dinf = box(b"dinf", full(b"dref", 0, 0, struct.pack(">I", 1), full(b"url ", 0, 1)))
+    empty = struct.pack(">I", 0)
+    stbl = box(b"stbl", full(b"stsd", 0, 0, struct.pack(">I", 1), sample_entry),
+               full(b"stts", 0, 0, empty), full(b"stsc", 0, 0, empty),
+               full(b"stsz", 0, 0, struct.pack(">II", 0, 0)), full(b"stco", 0, 0, empty))
+    trak = box(b"trak", tkhd, box(b"mdia", mdhd, hdlr, box(b"minf", media_header, dinf, stbl)))
+    mvhd = full(b"mvhd", 0, 0, struct.pack(">IIIIIH", 0, 0, 1000, 0, 0x10000, 0x100), bytes(10),
+                MATRIX, bytes(24), struct.pack(">I", 2))
+    trex = full(b"trex", 0, 0, struct.pack(">IIIII", 1, 1, 0, 0, trex_flags))
+    return box(b"moov", mvhd, trak, box(b"mvex", trex))
+
+
+def fragment(seq, dts, samples, tfhd_flags, tfhd_defaults, trun_flags, first_flags=None):
+    """samples: list of (duration, payload, flags, cto)"""
+    def moof(data_offset):
+        entries = b""
+        for duration, payload, flags, cto in samples:
+            if trun_flags & 0x100:
+                entries += struct.pack(">I", duration)
+            if trun_flags & 0x200:
+                entries += struct.pack(">I", len(payload))
+            if trun_flags & 0x400:
+                entries += struct.pack(">I", flags)
+            if trun_flags & 0x800:
+                entries += struct.pack(">i", cto)
+        head = struct.pack(">Ii", len(samples), data_offset)
+        if first_flags is not None:
+            head += struct.pack(">I", first_flags)
+        traf = box(b"traf",
+                   full(b"tfhd", 0, tfhd_flags, struct.pack(">I", 1), tfhd_defaults),
+                   full(b"tfdt", 1, 0, struct.pack(">Q", dts)),
+                   full(b"trun", 0, trun_flags, head, entries))
+        return box(b"moof", full(b"mfhd", 0, 0, struct.pack(">I", seq)), traf)
+
+    size = len(moof(0))
+    mdat = box(b"mdat", *(p for _, p, _, _ in samples))
+    return moof(size + 8) + mdat
+
+
+def video():
+    avcc = box(b"avcC", bytes([1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 4]), b"\x67\x64\x00\x1f",
+               bytes([1, 0, 4]), b"\x68\xee\x3c\x80")
+    avc1 = box(b"avc1", bytes(6), struct.pack(">H", 1), bytes(16), struct.pack(">HH", 64, 48),
+               struct.pack(">II", 0x480000, 0x480000), bytes(4), struct.pack(">H", 1), bytes(32),
+               struct.pack(">Hh", 0x18, -1), avcc)
+    out = box(b"ftyp", b"iso5", struct.pack(">I", 1), b"avc1iso5dashmp41")
+    out += moov(b"vide", 15360, 64, 48, full(b"vmhd", 0, 1, bytes(8)), avc1, 0x10000)
+    ctos = [1024, 2048, 512, 1024]
+    for f in range(2):
+        samples = [(512, bytes([0x56, f, i]) * (10 + i), 0 if i == 0 else 0x10000, ctos[i])
+                   for i in range(4)]
+        # first sample sync through first_sample_flags, rest from tfhd defaults
+        out += fragment(f + 1, f * 2048, samples, 0x20000 | 0x20, struct.pack(">I", 0x10000),
+                        0x1 | 0x4 | 0x100 | 0x200 | 0x800, first_flags=0x2000000)
+    return out
+
+
+def audio():
+    esds = full(b"esds", 0, 0, bytes([3, 25, 0, 2, 0, 4, 17, 0x40, 0x15]) + bytes(11)
+                + bytes([5, 2, 0x12, 0x10, 6, 1, 2]))
+    mp4a = box(b"mp4a", bytes(6), struct.pack(">H", 1), bytes(8), struct.pack(">HH", 2, 16),
+               bytes(4), struct.pack(">I", 44100 << 16), esds)
+    out = box(b"ftyp", b"iso5", struct.pack(">I", 1), b"mp4aiso5dashmp41")
+    out += moov(b"soun", 44100, 0, 0, full(b"smhd", 0, 0, bytes(4)), mp4a, 0)
+    for f in range(3):
+        samples = [(1024, bytes([0x41, f, i]) * (5 + i), 0, 0) for i in range(5)]
+        out += fragment(f + 1, f * 5120, samples, 0x20000 | 0x8, struct.pack(">I", 1024),
+                        0x1 | 0x200)
+    return out
+
+
+if __name__ == "__main__":
+    import os
+    here = os.path.dirname(os.path.abspath(__file__))
+    with open(os.path.join(here, "video.m4s"), "wb") as f:
+        f.write(video())
+    with open(os.path.join(here, "audio.m4s"), "wb") as f:
+        f.write(audio())
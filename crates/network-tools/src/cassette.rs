@@ -0,0 +1,434 @@
+use std::path::{Path, PathBuf};
+
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderValue, CONTENT_TYPE, SET_COOKIE};
+use reqwest::{Request, Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use url::Url;
+
+use super::client::{Middleware, Next};
+
+const REDACTED: &str = "REDACTED";
+
+/// one recorded request/response pair
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// scrubbed, with sorted query
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// without `Set-Cookie`
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// send for real and append every text response to the file
+    Record,
+    /// answer from the file, never touches the network
+    Replay,
+}
+
+#[derive(Debug, Default)]
+struct Tape {
+    interactions: Vec<Interaction>,
+    /// replayed at least once
+    used: Vec<bool>,
+}
+
+/// record/replay of http interactions into a json lines file, one interaction per line
+///
+/// only text responses (json, xml, text) are recorded, media passes through
+/// in record mode and is not available in replay mode. cookies never reach
+/// the file: request headers are not recorded, `Set-Cookie` is dropped and
+/// the `scrub_params` are replaced in query, form bodies and response text
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    scrub: Vec<String>,
+    ignore: Vec<String>,
+    tape: tokio::sync::Mutex<Tape>,
+    /// opened by the first recorded interaction, each one is appended as it comes
+    file: tokio::sync::Mutex<Option<tokio::fs::File>>,
+}
+
+impl Cassette {
+    /// start an empty cassette, `path` is overwritten by the first interaction
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self::new(path.into(), CassetteMode::Record, Tape::default())
+    }
+
+    pub async fn replay(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let content = tokio::fs::read_to_string(&path).await?;
+        let interactions = content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(serde_json::from_str::<Interaction>)
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        let tape = Tape {
+            used: vec![false; interactions.len()],
+            interactions,
+        };
+        Ok(Self::new(path, CassetteMode::Replay, tape))
+    }
+
+    fn new(path: PathBuf, mode: CassetteMode, tape: Tape) -> Self {
+        Self {
+            path,
+            mode,
+            scrub: vec![],
+            ignore: vec![],
+            tape: tokio::sync::Mutex::new(tape),
+            file: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// query and form params whose value is replaced by `REDACTED`
+    pub fn scrub_params(mut self, names: &[&str]) -> Self {
+        self.scrub.extend(names.iter().map(|n| n.to_string()));
+        self
+    }
+
+    /// query and form params left out of the recording, for values that change
+    /// every run like timestamps and signs
+    pub fn ignore_params(mut self, names: &[&str]) -> Self {
+        self.ignore.extend(names.iter().map(|n| n.to_string()));
+        self
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn interactions(&self) -> Vec<Interaction> {
+        self.tape.lock().await.interactions.clone()
+    }
+
+    /// `{name}=value` and `"{name}":"value"` of scrubbed params in response text,
+    /// login urls and refresh responses carry cookies in their body
+    fn scrub_text(&self, text: &str) -> String {
+        let mut text = text.to_owned();
+        for name in self.scrub.iter() {
+            let patterns = [
+                (format!("{}=", name), &['&', '"', ';', ' ', '\\'][..]),
+                (format!("\"{}\":\"", name), &['"'][..]),
+            ];
+            for (prefix, ends) in patterns {
+                let mut out = String::with_capacity(text.len());
+                let mut rest = text.as_str();
+                while let Some(i) = rest.find(&prefix) {
+                    let (head, tail) = rest.split_at(i + prefix.len());
+                    out.push_str(head);
+                    out.push_str(REDACTED);
+                    rest = &tail[tail.find(ends).unwrap_or(tail.len())..];
+                }
+                out.push_str(rest);
+                text = out;
+            }
+        }
+        text
+    }
+
+    /// `k=v` pairs without ignored params, scrubbed and sorted
+    fn normalize<'a>(
+        &self,
+        pairs: impl Iterator<Item = (String, String)> + 'a,
+    ) -> Vec<(String, String)> {
+        let mut pairs = pairs
+            .filter(|(k, _)| !self.ignore.contains(k))
+            .map(|(k, v)| {
+                if self.scrub.contains(&k) {
+                    (k, REDACTED.to_owned())
+                } else {
+                    (k, v)
+                }
+            })
+            .collect::<Vec<_>>();
+        pairs.sort();
+        pairs
+    }
+
+    fn recorded_request(&self, req: &Request) -> RecordedRequest {
+        let mut url = req.url().clone();
+        let query = self.normalize(url.query_pairs().into_owned());
+        url.set_query(None);
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        let is_form = req.headers().get(CONTENT_TYPE).is_some_and(|v| {
+            v.as_bytes()
+                .starts_with(b"application/x-www-form-urlencoded")
+        });
+        let body = req.body().and_then(|b| b.as_bytes()).map(|body| {
+            if is_form {
+                url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(self.normalize(url::form_urlencoded::parse(body).into_owned()))
+                    .finish()
+            } else {
+                String::from_utf8_lossy(body).into_owned()
+            }
+        });
+        RecordedRequest {
+            method: req.method().to_string(),
+            url: url.to_string(),
+            body,
+        }
+    }
+
+    async fn record_one(&self, req: Request, next: Next<'_>) -> reqwest::Result<Response> {
+        let recorded = self.recorded_request(&req);
+        let resp = next.run(req).await?;
+        let is_text = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| ["json", "text", "xml"].iter().any(|t| v.contains(t)));
+        if !is_text {
+            return Ok(resp);
+        }
+        let (status, url, headers) = (resp.status(), resp.url().clone(), resp.headers().clone());
+        let body = resp.bytes().await?;
+        let Ok(text) = std::str::from_utf8(&body) else {
+            return Ok(rebuild(
+                status.as_u16(),
+                &url,
+                headers.iter(),
+                body.to_vec(),
+            ));
+        };
+        let response = RecordedResponse {
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter(|(k, _)| *k != SET_COOKIE)
+                .filter_map(|(k, v)| Some((k.to_string(), self.scrub_text(v.to_str().ok()?))))
+                .collect(),
+            body: self.scrub_text(text),
+        };
+        let interaction = Interaction {
+            request: recorded,
+            response,
+        };
+        if let Err(e) = self.append(&interaction).await {
+            log::warn!("save cassette {} failed: {}", self.path.display(), e);
+        }
+        {
+            let mut tape = self.tape.lock().await;
+            tape.interactions.push(interaction);
+            tape.used.push(false);
+        }
+        Ok(rebuild(
+            status.as_u16(),
+            &url,
+            headers.iter(),
+            body.to_vec(),
+        ))
+    }
+
+    /// one line at the end of the file, which is truncated by the first one
+    async fn append(&self, interaction: &Interaction) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(interaction)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().await;
+        let file = match &mut *file {
+            Some(file) => file,
+            None => file.insert(tokio::fs::File::create(&self.path).await?),
+        };
+        file.write_all(&line).await?;
+        file.flush().await
+    }
+
+    /// first unused match, the last match once all are used
+    async fn replay_one(&self, req: Request) -> Response {
+        let recorded = self.recorded_request(&req);
+        let mut tape = self.tape.lock().await;
+        let matches = (0..tape.interactions.len())
+            .filter(|i| tape.interactions[*i].request == recorded)
+            .collect::<Vec<_>>();
+        let Some(i) = matches
+            .iter()
+            .find(|i| !tape.used[**i])
+            .or(matches.last())
+            .copied()
+        else {
+            log::warn!(
+                "cassette {} has no {} {}",
+                self.path.display(),
+                recorded.method,
+                recorded.url
+            );
+            let body = format!(
+                "no recorded interaction for {} {}",
+                recorded.method, recorded.url
+            );
+            return rebuild(404, req.url(), std::iter::empty(), body.into_bytes());
+        };
+        tape.used[i] = true;
+        let response = &tape.interactions[i].response;
+        let headers = response
+            .headers
+            .iter()
+            .filter_map(|(k, v)| Some((k.parse().ok()?, HeaderValue::from_str(v).ok()?)))
+            .collect::<reqwest::header::HeaderMap>();
+        rebuild(
+            response.status,
+            req.url(),
+            headers.iter(),
+            response.body.clone().into_bytes(),
+        )
+    }
+}
+
+fn rebuild<'a>(
+    status: u16,
+    url: &Url,
+    headers: impl Iterator<Item = (&'a reqwest::header::HeaderName, &'a HeaderValue)>,
+    body: Vec<u8>,
+) -> Response {
+    let mut builder = http::Response::builder().status(status).url(url.clone());
+    for (k, v) in headers {
+        builder = builder.header(k, v);
+    }
+    Response::from(builder.body(body).expect("rebuild recorded response"))
+}
+
+impl Middleware for Cassette {
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, reqwest::Result<Response>> {
+        Box::pin(async move {
+            match self.mode {
+                CassetteMode::Record => self.record_one(req, next).await,
+                CassetteMode::Replay => Ok(self.replay_one(req).await),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Client;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn body(n: usize, secret: &str) -> String {
+        format!(
+            r#"{{"n":{},"url":"https://a?csrf={}\u0026b=1","csrf":"{}"}}"#,
+            n, secret, secret
+        )
+    }
+
+    /// answers the `n`th connection with `body(n, "secret")` and a session cookie
+    async fn serve(count: usize) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for n in 0..count {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let _ = stream.read(&mut buf).await.unwrap();
+                let body = body(n, "secret");
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nSet-Cookie: SESSDATA=secret\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() -> Result<(), Box<dyn std::error::Error>> {
+        let addr = serve(3).await;
+        let path = std::env::temp_dir().join(format!("cassette-{}.json", std::process::id()));
+        let client = Client::builder(reqwest::Client::new())
+            .with(
+                Cassette::record(&path)
+                    .scrub_params(&["csrf"])
+                    .ignore_params(&["wts"]),
+            )
+            .build();
+        let url = format!("http://{}/x/a?wts=1&b=2&a=1", addr);
+        assert_eq!(
+            client.get(&url).send().await?.text().await?,
+            body(0, "secret")
+        );
+        assert_eq!(
+            client.get(&url).send().await?.text().await?,
+            body(1, "secret")
+        );
+        let resp = client
+            .post(format!("http://{}/x/b", addr))
+            .form(&[("csrf", "secret"), ("id", "1")])
+            .send()
+            .await?;
+        assert_eq!(resp.url().path(), "/x/b");
+        assert_eq!(resp.text().await?, body(2, "secret"));
+
+        let content = tokio::fs::read_to_string(&path).await?;
+        assert!(!content.contains("secret"));
+        assert!(!content.contains("wts"));
+        assert!(content.contains("a=1&b=2"));
+        assert_eq!(content.lines().count(), 3);
+
+        // the server is gone, everything comes from the file
+        let client = Client::builder(reqwest::Client::new())
+            .with(
+                Cassette::replay(&path)
+                    .await?
+                    .scrub_params(&["csrf"])
+                    .ignore_params(&["wts"]),
+            )
+            .build();
+        let url = format!("http://{}/x/a?a=1&b=2&wts=2", addr);
+        assert_eq!(
+            client.get(&url).send().await?.text().await?,
+            body(0, REDACTED)
+        );
+        assert_eq!(
+            client.get(&url).send().await?.text().await?,
+            body(1, REDACTED)
+        );
+        // repeats the last one when out of recordings
+        assert_eq!(
+            client.get(&url).send().await?.text().await?,
+            body(1, REDACTED)
+        );
+        let resp = client
+            .post(format!("http://{}/x/b", addr))
+            .form(&[("csrf", "other"), ("id", "1")])
+            .send()
+            .await?;
+        assert!(resp.headers().get(SET_COOKIE).is_none());
+        assert_eq!(resp.text().await?, body(2, REDACTED));
+        let resp = client.get(format!("http://{}/x/c", addr)).send().await?;
+        assert_eq!(resp.status(), 404);
+
+        tokio::fs::remove_file(&path).await?;
+        Ok(())
+    }
+}
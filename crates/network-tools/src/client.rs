@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use reqwest::{IntoUrl, Method, Request, Response};
+
+/// hook around every request sent by a `Client`
+///
+/// middlewares run in the order they are added, each one decides whether and
+/// how many times to call `next`
+pub trait Middleware: Send + Sync + 'static {
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, reqwest::Result<Response>>;
+}
+
+/// the rest of the middleware chain, ends with sending the request
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    client: &'a reqwest::Client,
+    middlewares: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub fn run(self, req: Request) -> BoxFuture<'a, reqwest::Result<Response>> {
+        match self.middlewares.split_first() {
+            Some((middleware, rest)) => middleware.handle(
+                req,
+                Next {
+                    client: self.client,
+                    middlewares: rest,
+                },
+            ),
+            None => Box::pin(self.client.execute(req)),
+        }
+    }
+}
+
+/// `reqwest::Client` with a middleware chain, cheap to clone
+#[derive(Clone)]
+pub struct Client {
+    inner: reqwest::Client,
+    middlewares: Arc<[Arc<dyn Middleware>]>,
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("inner", &self.inner)
+            .field("middlewares", &self.middlewares.len())
+            .finish()
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(reqwest::Client::default())
+    }
+}
+
+impl From<reqwest::Client> for Client {
+    fn from(inner: reqwest::Client) -> Self {
+        Self::new(inner)
+    }
+}
+
+impl Client {
+    /// no middleware, same as the plain `inner`
+    pub fn new(inner: reqwest::Client) -> Self {
+        Self::builder(inner).build()
+    }
+
+    pub fn builder(inner: reqwest::Client) -> ClientBuilder {
+        ClientBuilder {
+            inner,
+            middlewares: vec![],
+        }
+    }
+
+    pub fn inner(&self) -> &reqwest::Client {
+        &self.inner
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    pub fn head(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.request(Method::HEAD, url)
+    }
+
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> RequestBuilder {
+        RequestBuilder {
+            client: self.clone(),
+            inner: self.inner.request(method, url),
+        }
+    }
+
+    /// send through the middleware chain
+    pub async fn execute(&self, req: Request) -> reqwest::Result<Response> {
+        Next {
+            client: &self.inner,
+            middlewares: &self.middlewares,
+        }
+        .run(req)
+        .await
+    }
+}
+
+pub struct ClientBuilder {
+    inner: reqwest::Client,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl ClientBuilder {
+    /// append a middleware, the first added sees the request first
+    pub fn with(self, middleware: impl Middleware) -> Self {
+        self.with_arc(Arc::new(middleware))
+    }
+
+    pub fn with_arc(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.middlewares.push(middleware);
+        self
+    }
+
+    pub fn build(self) -> Client {
+        Client {
+            inner: self.inner,
+            middlewares: self.middlewares.into(),
+        }
+    }
+}
+
+/// `reqwest::RequestBuilder` sent through the `Client` middlewares
+pub struct RequestBuilder {
+    client: Client,
+    inner: reqwest::RequestBuilder,
+}
+
+impl RequestBuilder {
+    pub fn header<K, V>(self, key: K, value: V) -> Self
+    where
+        reqwest::header::HeaderName: TryFrom<K>,
+        <reqwest::header::HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        reqwest::header::HeaderValue: TryFrom<V>,
+        <reqwest::header::HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        self.map(|b| b.header(key, value))
+    }
+
+    pub fn headers(self, headers: reqwest::header::HeaderMap) -> Self {
+        self.map(|b| b.headers(headers))
+    }
+
+    pub fn query<T: serde::Serialize + ?Sized>(self, query: &T) -> Self {
+        self.map(|b| b.query(query))
+    }
+
+    pub fn form<T: serde::Serialize + ?Sized>(self, form: &T) -> Self {
+        self.map(|b| b.form(form))
+    }
+
+    pub fn json<T: serde::Serialize + ?Sized>(self, json: &T) -> Self {
+        self.map(|b| b.json(json))
+    }
+
+    pub fn body(self, body: impl Into<reqwest::Body>) -> Self {
+        self.map(|b| b.body(body))
+    }
+
+    pub fn timeout(self, timeout: std::time::Duration) -> Self {
+        self.map(|b| b.timeout(timeout))
+    }
+
+    pub fn build(self) -> reqwest::Result<Request> {
+        self.inner.build()
+    }
+
+    pub async fn send(self) -> reqwest::Result<Response> {
+        let req = self.inner.build()?;
+        self.client.execute(req).await
+    }
+
+    fn map(self, f: impl FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder) -> Self {
+        Self {
+            client: self.client,
+            inner: f(self.inner),
+        }
+    }
+}
@@ -1,14 +1,13 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
-}
+mod cassette;
+mod client;
+mod middleware;
+mod proxy;
+mod rate_limit;
+mod retry;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use cassette::*;
+pub use client::*;
+pub use middleware::*;
+pub use proxy::*;
+pub use rate_limit::*;
+pub use retry::*;
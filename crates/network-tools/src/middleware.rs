@@ -0,0 +1,270 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Request, Response};
+
+use super::client::{Middleware, Next};
+
+/// headers added to every request that doesn't set them itself
+#[derive(Debug, Clone, Default)]
+pub struct DefaultHeaders {
+    headers: HeaderMap,
+}
+
+impl DefaultHeaders {
+    pub fn new(headers: HeaderMap) -> Self {
+        Self { headers }
+    }
+
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+}
+
+impl Middleware for DefaultHeaders {
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, reqwest::Result<Response>> {
+        for (name, value) in self.headers.iter() {
+            if !req.headers().contains_key(name) {
+                req.headers_mut().insert(name.clone(), value.clone());
+            }
+        }
+        next.run(req)
+    }
+}
+
+/// cookie store handled as a middleware, for clients built without `cookie_provider`
+///
+/// redirects followed inside the client don't go through it
+#[derive(Clone)]
+pub struct Cookies {
+    store: Arc<dyn reqwest::cookie::CookieStore>,
+}
+
+impl Cookies {
+    pub fn new(store: Arc<dyn reqwest::cookie::CookieStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl Middleware for Cookies {
+    fn handle<'a>(
+        &'a self,
+        mut req: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, reqwest::Result<Response>> {
+        Box::pin(async move {
+            let url = req.url().clone();
+            if !req.headers().contains_key(reqwest::header::COOKIE) {
+                if let Some(cookies) = self.store.cookies(&url) {
+                    req.headers_mut().insert(reqwest::header::COOKIE, cookies);
+                }
+            }
+            let resp = next.run(req).await?;
+            let mut set_cookies = resp.headers().get_all(reqwest::header::SET_COOKIE).iter();
+            self.store.set_cookies(&mut set_cookies, &url);
+            Ok(resp)
+        })
+    }
+}
+
+/// `log` every request with its status and latency
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logger;
+
+impl Middleware for Logger {
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, reqwest::Result<Response>> {
+        Box::pin(async move {
+            let (method, url) = (req.method().clone(), req.url().clone());
+            let start = Instant::now();
+            let res = next.run(req).await;
+            match &res {
+                Ok(resp) => log::debug!(
+                    "{} {} -> {} in {:?}",
+                    method,
+                    url,
+                    resp.status(),
+                    start.elapsed()
+                ),
+                Err(e) => log::warn!("{} {} failed in {:?}: {}", method, url, start.elapsed(), e),
+            }
+            res
+        })
+    }
+}
+
+/// request counters, shared by every clone
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    client_errors: AtomicU64,
+    server_errors: AtomicU64,
+    latency_micros: AtomicU64,
+}
+
+/// counters of a `Metrics` at some moment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub requests: u64,
+    /// failed without a response
+    pub errors: u64,
+    /// 4xx responses
+    pub client_errors: u64,
+    /// 5xx responses
+    pub server_errors: u64,
+    /// sum of the time to response headers
+    pub latency: Duration,
+}
+
+impl Metrics {
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let c = &self.inner;
+        MetricsSnapshot {
+            requests: c.requests.load(Ordering::Relaxed),
+            errors: c.errors.load(Ordering::Relaxed),
+            client_errors: c.client_errors.load(Ordering::Relaxed),
+            server_errors: c.server_errors.load(Ordering::Relaxed),
+            latency: Duration::from_micros(c.latency_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Middleware for Metrics {
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, reqwest::Result<Response>> {
+        Box::pin(async move {
+            let c = &self.inner;
+            let start = Instant::now();
+            let res = next.run(req).await;
+            c.requests.fetch_add(1, Ordering::Relaxed);
+            c.latency_micros
+                .fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+            let counter = match &res {
+                Ok(resp) if resp.status().is_client_error() => Some(&c.client_errors),
+                Ok(resp) if resp.status().is_server_error() => Some(&c.server_errors),
+                Ok(_) => None,
+                Err(_) => Some(&c.errors),
+            };
+            if let Some(counter) = counter {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, Retry, RetryPolicyBuilder};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// answers the `n`th connection with `statuses[n]`, echoes the `x-echo` or `cookie`
+    /// header back and sets cookie `b=2`
+    async fn serve(statuses: Vec<u16>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let echo = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("x-echo: "))
+                    .or_else(|| head.lines().find_map(|l| l.strip_prefix("cookie: ")))
+                    .unwrap_or_default()
+                    .trim()
+                    .to_owned();
+                let resp = format!(
+                    "HTTP/1.1 {} MOCK\r\nSet-Cookie: b=2\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    echo.len(),
+                    echo
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_default_headers() -> reqwest::Result<()> {
+        let addr = serve(vec![200, 200]).await;
+        let client = Client::builder(reqwest::Client::new())
+            .with(DefaultHeaders::default().header(
+                HeaderName::from_static("x-echo"),
+                HeaderValue::from_static("default"),
+            ))
+            .build();
+        let url = format!("http://{}/", addr);
+        assert_eq!(client.get(&url).send().await?.text().await?, "default");
+        let resp = client.get(&url).header("x-echo", "mine").send().await?;
+        assert_eq!(resp.text().await?, "mine");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cookies() -> reqwest::Result<()> {
+        let addr = serve(vec![200, 200]).await;
+        let url = format!("http://{}/", addr).parse::<reqwest::Url>().unwrap();
+        let jar = Arc::new(reqwest::cookie::Jar::default());
+        jar.add_cookie_str("a=1", &url);
+        let client = Client::builder(reqwest::Client::new())
+            .with(Cookies::new(jar))
+            .build();
+        assert_eq!(client.get(url.clone()).send().await?.text().await?, "a=1");
+        // `b` set by the first response, the jar has no order
+        let body = client.get(url).send().await?.text().await?;
+        let mut cookies = body.split("; ").collect::<Vec<_>>();
+        cookies.sort();
+        assert_eq!(cookies, vec!["a=1", "b=2"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_and_metrics() -> reqwest::Result<()> {
+        let addr = serve(vec![503, 502, 200, 404]).await;
+        let metrics = Metrics::default();
+        let policy = RetryPolicyBuilder::default()
+            .base_delay(Duration::from_millis(1))
+            .build()
+            .unwrap();
+        // metrics after retry sees every attempt
+        let client = Client::builder(reqwest::Client::new())
+            .with(Logger)
+            .with(Retry::new(policy))
+            .with(metrics.clone())
+            .build();
+        let url = format!("http://{}/", addr);
+        assert_eq!(client.get(&url).send().await?.status(), 200);
+        assert_eq!(client.get(&url).send().await?.status(), 404);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.requests, 4);
+        assert_eq!(snapshot.server_errors, 2);
+        assert_eq!(snapshot.client_errors, 1);
+        assert_eq!(snapshot.errors, 0);
+        Ok(())
+    }
+}
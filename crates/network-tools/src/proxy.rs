@@ -0,0 +1,207 @@
+use url::Url;
+
+/// proxy routing by request host, first matching rule wins
+///
+/// proxies are `http://`, `https://`, `socks5://` or `socks5h://` urls,
+/// `socks5h` resolves hostnames on the proxy side
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// for hosts no rule matches, `None` is direct
+    default: Option<Url>,
+    /// `(domain, proxy)`, a domain also matches its subdomains
+    rules: Vec<(String, Option<Url>)>,
+}
+
+impl ProxyConfig {
+    /// every request through `proxy`
+    pub fn all(proxy: &str) -> Result<Self, url::ParseError> {
+        Self::default().default_proxy(Some(proxy))
+    }
+
+    /// proxy of unmatched hosts, `None` to connect directly
+    pub fn default_proxy(mut self, proxy: Option<&str>) -> Result<Self, url::ParseError> {
+        self.default = proxy.map(Url::parse).transpose()?;
+        Ok(self)
+    }
+
+    /// send `domain` and its subdomains through `proxy`, `None` to connect directly
+    pub fn route(self, domain: &str, proxy: Option<&str>) -> Result<Self, url::ParseError> {
+        self.route_all(&[domain], proxy)
+    }
+
+    pub fn route_all(
+        mut self,
+        domains: &[&str],
+        proxy: Option<&str>,
+    ) -> Result<Self, url::ParseError> {
+        let proxy = proxy.map(Url::parse).transpose()?;
+        for domain in domains {
+            let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+            self.rules.push((domain, proxy.clone()));
+        }
+        Ok(self)
+    }
+
+    /// true if nothing is ever proxied
+    pub fn is_direct(&self) -> bool {
+        self.default.is_none() && self.rules.iter().all(|(_, p)| p.is_none())
+    }
+
+    /// proxy for a request to `url`
+    pub fn resolve(&self, url: &Url) -> Option<&Url> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let matches = |domain: &str| {
+            host == domain
+                || host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.ends_with('.'))
+        };
+        match self.rules.iter().find(|(domain, _)| matches(domain)) {
+            Some((_, proxy)) => proxy.as_ref(),
+            None => self.default.as_ref(),
+        }
+    }
+
+    /// install into a client, replaces the proxies from the environment
+    pub fn apply(self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if self.is_direct() {
+            return builder.no_proxy();
+        }
+        builder.proxy(reqwest::Proxy::custom(move |url| {
+            self.resolve(url).cloned()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_resolve() -> Result<(), url::ParseError> {
+        let config = ProxyConfig::all("http://127.0.0.1:8080")?
+            .route("api.example.com", None)?
+            .route_all(
+                &["cdn.example", ".video.example"],
+                Some("socks5h://127.0.0.1:1080"),
+            )?;
+        let resolve = |url: &str| {
+            config
+                .resolve(&Url::parse(url).unwrap())
+                .map(|u| u.to_string())
+        };
+        assert_eq!(resolve("https://api.example.com/x"), None);
+        assert_eq!(
+            resolve("https://up.cdn.example/a.m4s"),
+            Some("socks5h://127.0.0.1:1080".to_owned())
+        );
+        assert_eq!(
+            resolve("https://VIDEO.example/a.m4s"),
+            Some("socks5h://127.0.0.1:1080".to_owned())
+        );
+        // not a subdomain
+        assert_eq!(
+            resolve("https://notcdn.example/"),
+            Some("http://127.0.0.1:8080/".to_owned())
+        );
+        assert!(ProxyConfig::default().is_direct());
+        assert!(ProxyConfig::all("not a url").is_err());
+        Ok(())
+    }
+
+    /// answer one request with `name` as body, returns the request line
+    async fn reply(mut stream: tokio::net::TcpStream, name: &str) -> String {
+        let mut buf = vec![0; 4096];
+        let n = stream.read(&mut buf).await.unwrap();
+        let head = String::from_utf8_lossy(&buf[..n]).into_owned();
+        let resp = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            name.len(),
+            name
+        );
+        stream.write_all(resp.as_bytes()).await.unwrap();
+        head.lines().next().unwrap_or_default().to_owned()
+    }
+
+    /// plain http proxy, answers by itself and reports the request line
+    async fn http_proxy() -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let _ = tx.send(reply(stream, "http-proxy").await);
+            }
+        });
+        (addr, rx)
+    }
+
+    /// socks5 without auth, answers by itself and reports the target
+    async fn socks5_proxy() -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                // greeting: ver, nmethods, methods
+                let mut head = [0u8; 2];
+                stream.read_exact(&mut head).await.unwrap();
+                let mut methods = vec![0; head[1] as usize];
+                stream.read_exact(&mut methods).await.unwrap();
+                stream.write_all(&[5, 0]).await.unwrap();
+                // connect: ver, cmd, rsv, atyp=domain, len, domain, port
+                let mut req = [0u8; 5];
+                stream.read_exact(&mut req).await.unwrap();
+                assert_eq!(req[3], 3, "socks5h sends the domain");
+                let mut domain = vec![0; req[4] as usize + 2];
+                stream.read_exact(&mut domain).await.unwrap();
+                let port = u16::from_be_bytes([domain[domain.len() - 2], domain[domain.len() - 1]]);
+                let host = String::from_utf8_lossy(&domain[..domain.len() - 2]).into_owned();
+                stream
+                    .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+                    .await
+                    .unwrap();
+                reply(stream, "socks5").await;
+                let _ = tx.send(format!("{}:{}", host, port));
+            }
+        });
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_routing() -> Result<(), Box<dyn std::error::Error>> {
+        let (http, mut http_rx) = http_proxy().await;
+        let (socks, mut socks_rx) = socks5_proxy().await;
+        let config = ProxyConfig::default()
+            .route("api.example", Some(&format!("http://{}", http)))?
+            .route("cdn.example", Some(&format!("socks5h://{}", socks)))?;
+        let client = config.apply(reqwest::Client::builder()).build()?;
+
+        let body = client
+            .get("http://api.example/x?a=1")
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert_eq!(body, "http-proxy");
+        assert_eq!(
+            http_rx.recv().await.as_deref(),
+            Some("GET http://api.example/x?a=1 HTTP/1.1")
+        );
+
+        let body = client
+            .get("http://up.cdn.example/a.m4s")
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert_eq!(body, "socks5");
+        assert_eq!(socks_rx.recv().await.as_deref(), Some("up.cdn.example:80"));
+
+        // unmatched hosts connect directly and fail to resolve
+        assert!(client.get("http://other.invalid/").send().await.is_err());
+        assert!(http_rx.try_recv().is_err() && socks_rx.try_recv().is_err());
+        Ok(())
+    }
+}
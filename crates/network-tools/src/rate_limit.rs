@@ -0,0 +1,168 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use futures::future::BoxFuture;
+use reqwest::{Request, Response};
+
+use super::client::{Middleware, Next};
+
+/// token bucket shared by every clone, one token per byte
+///
+/// the bucket holds at most one second of tokens, a take larger than that
+/// borrows from the future and waits for the debt to be paid back
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    inner: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// bytes per second, `None` is unlimited
+    rate: Option<u64>,
+    /// negative while in debt
+    tokens: f64,
+    last: Instant,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            rate: None,
+            tokens: 0.0,
+            last: Instant::now(),
+        }
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        if let Some(rate) = self.rate {
+            let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        }
+        self.last = now;
+    }
+}
+
+impl RateLimiter {
+    /// `None` never waits
+    pub fn new(rate: Option<u64>) -> Self {
+        let limiter = Self::default();
+        limiter.set_rate(rate);
+        limiter
+    }
+
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn rate(&self) -> Option<u64> {
+        self.inner.lock().expect("rate limiter poisoned").rate
+    }
+
+    /// change the rate of every clone, takes effect for the next `acquire`
+    pub fn set_rate(&self, rate: Option<u64>) {
+        let mut bucket = self.inner.lock().expect("rate limiter poisoned");
+        bucket.refill(Instant::now());
+        bucket.rate = rate.filter(|r| *r > 0);
+        bucket.tokens = match bucket.rate {
+            Some(rate) => bucket.tokens.min(rate as f64),
+            None => 0.0,
+        };
+    }
+
+    /// take `bytes` tokens, waiting until the bucket can afford them
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.inner.lock().expect("rate limiter poisoned");
+            bucket.refill(Instant::now());
+            match bucket.rate {
+                Some(rate) => {
+                    bucket.tokens -= bytes as f64;
+                    if bucket.tokens < 0.0 {
+                        Duration::from_secs_f64(-bucket.tokens / rate as f64)
+                    } else {
+                        Duration::ZERO
+                    }
+                }
+                None => Duration::ZERO,
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// charges the `Content-Length` of every response before handing it out,
+/// streaming downloads should `acquire` per received chunk instead
+impl Middleware for RateLimiter {
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, reqwest::Result<Response>> {
+        Box::pin(async move {
+            let resp = next.run(req).await?;
+            self.acquire(resp.content_length().unwrap_or(0)).await;
+            Ok(resp)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the clock is paused and only moves by sleeping, elapsed times are exact
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unlimited() {
+        let limiter = RateLimiter::unlimited();
+        let start = Instant::now();
+        limiter.acquire(u32::MAX as u64).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(RateLimiter::new(Some(0)).rate(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shared_rate() {
+        // 4 tasks share 100KB/s, 20KB in total takes 0.2s
+        let limiter = RateLimiter::new(Some(100_000));
+        let start = Instant::now();
+        let tasks = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    for _ in 0..5 {
+                        limiter.acquire(1_000).await;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(199), "{:?}", elapsed);
+        assert!(elapsed <= Duration::from_millis(201), "{:?}", elapsed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_rate() {
+        let limiter = RateLimiter::new(Some(1_000));
+        limiter.set_rate(None);
+        let start = Instant::now();
+        limiter.acquire(1_000_000).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(limiter.rate(), None);
+
+        limiter.set_rate(Some(1_000_000));
+        let start = Instant::now();
+        limiter.acquire(100_000).await;
+        assert_eq!(start.elapsed().as_millis(), 100);
+        assert_eq!(limiter.clone().rate(), Some(1_000_000));
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use derive_builder::Builder;
+use derive_getters::Getters;
+use futures::future::BoxFuture;
+use reqwest::{Request, Response, StatusCode};
+
+use super::client::{Middleware, Next};
+
+/// how many times and how long to wait before retrying
+#[derive(Debug, Clone, Getters, Builder)]
+#[builder(default)]
+pub struct RetryPolicy {
+    /// retries after the first attempt
+    max_retries: u32,
+    /// delay before the first retry, doubled every retry
+    base_delay: Duration,
+    max_delay: Duration,
+    /// randomize delays into `[delay / 2, delay]`
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// no retry at all
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// delay before the `retry`th retry, counted from 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        if self.jitter {
+            use rand::Rng;
+            let half = delay / 2;
+            half + half.mul_f64(rand::thread_rng().gen::<f64>())
+        } else {
+            delay
+        }
+    }
+}
+
+/// 5xx, 429 and 408 are worth another attempt
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// timeouts, connection failures and broken bodies, or a retryable status
+pub fn is_retryable_error(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => is_retryable_status(status),
+        None => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+    }
+}
+
+/// resend requests failed with a retryable error or status,
+/// requests with a streaming body can't be cloned and are sent once
+#[derive(Debug, Clone, Default)]
+pub struct Retry {
+    policy: RetryPolicy,
+}
+
+impl Retry {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self { policy }
+    }
+}
+
+impl Middleware for Retry {
+    fn handle<'a>(
+        &'a self,
+        req: Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, reqwest::Result<Response>> {
+        Box::pin(async move {
+            let mut retry = 0;
+            loop {
+                let attempt = match req.try_clone() {
+                    Some(attempt) if retry < self.policy.max_retries => attempt,
+                    _ => return next.run(req).await,
+                };
+                match next.run(attempt).await {
+                    Ok(resp) if !is_retryable_status(resp.status()) => return Ok(resp),
+                    Err(e) if !is_retryable_error(&e) => return Err(e),
+                    Ok(resp) => {
+                        log::debug!("retry {} {}: {}", req.method(), req.url(), resp.status())
+                    }
+                    Err(e) => log::debug!("retry {} {}: {}", req.method(), req.url(), e),
+                }
+                tokio::time::sleep(self.policy.delay(retry)).await;
+                retry += 1;
+            }
+        })
+    }
+}